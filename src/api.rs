@@ -1,8 +1,26 @@
 use actix_web::web;
 
+pub mod archive;
+pub mod events;
+pub mod history;
+pub mod projects;
 pub mod state;
 pub mod todos;
+pub mod trash;
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
-    cfg.configure(todos::routes);
+    // Project management routes must come before the scope, which would
+    // otherwise swallow `/projects/{name}` and answer it with a 404
+    cfg.configure(events::routes)
+        .configure(history::routes)
+        .configure(projects::routes)
+        .configure(todos::routes)
+        .configure(trash::routes)
+        .configure(archive::routes)
+        .service(
+            web::scope("/projects/{project}")
+                .configure(todos::routes)
+                .configure(trash::routes)
+                .configure(archive::routes),
+        );
 }
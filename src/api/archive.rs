@@ -0,0 +1,55 @@
+use super::projects::ProjectName;
+use super::state::AppState;
+use crate::shared::output::TodoResponse;
+use crate::shared::todo::start_of_day;
+use actix_web::{HttpResponse, Responder, get, post, web};
+use chrono::NaiveDate;
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list).service(archive);
+}
+
+#[derive(serde::Deserialize)]
+pub struct ArchiveQuery {
+    /// Only archive todos completed before this date
+    before: Option<NaiveDate>,
+}
+
+#[derive(serde::Serialize)]
+struct ArchiveResponse {
+    archived: usize,
+}
+
+#[get("/archive")]
+async fn list(state: web::Data<AppState>, project: ProjectName) -> impl Responder {
+    let archived = match state.load_archive() {
+        Ok(archived) => archived,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let body: Vec<TodoResponse> = archived
+        .project(&project.0)
+        .map(|todos| todos.list().iter().map(TodoResponse::from).collect())
+        .unwrap_or_default();
+    HttpResponse::Ok().json(body)
+}
+
+#[post("/archive")]
+async fn archive(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    query: web::Query<ArchiveQuery>,
+) -> impl Responder {
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    if let Err(e) = store.project(&project.0) {
+        return HttpResponse::NotFound().body(e);
+    }
+
+    match state.archive(&mut store, &project.0, query.before.map(start_of_day)) {
+        Ok(archived) => HttpResponse::Ok().json(ArchiveResponse { archived }),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
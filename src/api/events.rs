@@ -0,0 +1,20 @@
+use super::state::AppState;
+use actix_web::{HttpResponse, Responder, get, web};
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list);
+}
+
+#[derive(serde::Deserialize)]
+struct EventsQuery {
+    #[serde(default)]
+    since: u64,
+}
+
+#[get("/events")]
+async fn list(state: web::Data<AppState>, query: web::Query<EventsQuery>) -> impl Responder {
+    match state.events(query.since) {
+        Ok(events) => HttpResponse::Ok().json(events),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,30 @@
+use super::state::AppState;
+use crate::shared::storage::StorageError;
+use actix_web::{HttpResponse, Responder, post, web};
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(undo).service(redo);
+}
+
+#[derive(serde::Serialize)]
+struct StepResponse {
+    operation: String,
+}
+
+#[post("/undo")]
+async fn undo(state: web::Data<AppState>) -> impl Responder {
+    respond(state.undo(), "Nothing to undo")
+}
+
+#[post("/redo")]
+async fn redo(state: web::Data<AppState>) -> impl Responder {
+    respond(state.redo(), "Nothing to redo")
+}
+
+fn respond(result: Result<Option<String>, StorageError>, empty: &str) -> HttpResponse {
+    match result {
+        Ok(Some(operation)) => HttpResponse::Ok().json(StepResponse { operation }),
+        Ok(None) => HttpResponse::Conflict().body(empty.to_string()),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
@@ -0,0 +1,118 @@
+use super::state::AppState;
+use crate::shared::store::DEFAULT_PROJECT;
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest, HttpResponse, Responder, delete, get, post, put, web};
+use std::future::{Ready, ready};
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list)
+        .service(create)
+        .service(rename)
+        .service(delete);
+}
+
+/// Project a todo route operates on, taken from the `/projects/{project}`
+/// scope or the default project for plain `/todos` routes
+pub struct ProjectName(pub String);
+
+impl FromRequest for ProjectName {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let name = req.match_info().get("project").unwrap_or(DEFAULT_PROJECT);
+        ready(Ok(ProjectName(name.to_string())))
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct ProjectPayload {
+    pub name: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct ProjectResponse {
+    pub name: String,
+    pub open: usize,
+    pub done: usize,
+}
+
+#[get("/projects")]
+async fn list(state: web::Data<AppState>) -> HttpResponse {
+    let store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    let response: Vec<ProjectResponse> = store
+        .names()
+        .filter_map(|name| {
+            let todos = store.project(name).ok()?;
+            Some(ProjectResponse {
+                name: name.clone(),
+                open: todos.todo().count(),
+                done: todos.done().count(),
+            })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(response)
+}
+
+#[post("/projects")]
+async fn create(state: web::Data<AppState>, body: web::Json<ProjectPayload>) -> impl Responder {
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    match store.create(&body.name) {
+        Ok(_) => {
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            HttpResponse::Created().finish()
+        }
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+#[put("/projects/{name}")]
+async fn rename(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+    body: web::Json<ProjectPayload>,
+) -> impl Responder {
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    match store.rename(&name, &body.name) {
+        Ok(_) => {
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            HttpResponse::Ok().finish()
+        }
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+#[delete("/projects/{name}")]
+async fn delete(state: web::Data<AppState>, name: web::Path<String>) -> impl Responder {
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    match store.delete(&name) {
+        Ok(_) => {
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => HttpResponse::NotFound().body(e),
+    }
+}
@@ -1,4 +1,180 @@
-use crate::shared::todo::TodoList;
-use std::sync::{Arc, Mutex};
+use crate::shared::config::Config;
+use crate::shared::events::Event;
+use crate::shared::file::{FileLock, Stamp};
+use crate::shared::history::{self, History};
+use crate::shared::storage::{Storage, StorageError};
+use crate::shared::store::Store;
+use chrono::{DateTime, Utc};
+use std::cell::Cell;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
 
-pub type AppState = Arc<Mutex<TodoList>>;
+pub struct AppState {
+    store: Mutex<Store>,
+    storage: Box<dyn Storage>,
+    stamp: Mutex<Option<Stamp>>,
+    config: Config,
+}
+
+/// Store locked both in memory and on disk, see `AppState::lock`. Changes
+/// that were not saved when it is dropped, say because the handler failed
+/// half way, are rolled back.
+pub struct StoreGuard<'a> {
+    store: MutexGuard<'a, Store>,
+    // As loaded, to record in the undo history when saving
+    before: Store,
+    saved: Cell<bool>,
+    _lock: FileLock,
+}
+
+impl AppState {
+    pub fn new(store: Store, storage: Box<dyn Storage>, config: Config) -> Self {
+        AppState {
+            stamp: Mutex::new(storage.stamp()),
+            store: Mutex::new(store),
+            storage,
+            config,
+        }
+    }
+
+    /// Takes the file lock and reloads the store if another process, such as
+    /// todo-cli, has written it since we last loaded or saved it
+    pub fn lock(&self) -> Result<StoreGuard<'_>, StorageError> {
+        self.acquire(true)
+    }
+
+    // Auto-archiving is skipped for undo and redo, as it would record a
+    // change of its own for them to revert
+    fn acquire(&self, archive: bool) -> Result<StoreGuard<'_>, StorageError> {
+        let lock = self.storage.lock()?;
+        let mut store = self.store.lock().unwrap();
+        let mut stamp = self.stamp.lock().unwrap();
+
+        let current = self.storage.stamp();
+        if current != *stamp {
+            *store = self.storage.load()?;
+            *stamp = current;
+        }
+        if archive
+            && let Some(cutoff) = self.config.archive_cutoff()
+            && self.storage.archive(&mut store, None, Some(cutoff))? > 0
+        {
+            *stamp = self.storage.stamp();
+        }
+        // Saved along with the next change
+        if let Some(cutoff) = self.config.trash_cutoff() {
+            store.purge_trash(cutoff);
+        }
+
+        Ok(StoreGuard {
+            before: store.clone(),
+            store,
+            saved: Cell::new(false),
+            _lock: lock,
+        })
+    }
+
+    /// Writes the store and records the change in the undo history
+    pub fn save(&self, store: &StoreGuard) -> Result<(), StorageError> {
+        let mut history = self.storage.load_history()?;
+        history.record(
+            history::describe(&store.before, store),
+            &store.before,
+            store,
+        );
+
+        self.write(store)?;
+        store.saved.set(true);
+        self.storage.save_history(&history)
+    }
+
+    /// Moves done todos of `project` into the archive and saves both stores
+    pub fn archive(
+        &self,
+        store: &mut StoreGuard,
+        project: &str,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<usize, StorageError> {
+        let count = self
+            .storage
+            .archive(&mut store.store, Some(project), before)?;
+        if count > 0 {
+            *self.stamp.lock().unwrap() = self.storage.stamp();
+            store.saved.set(true);
+        }
+
+        Ok(count)
+    }
+
+    pub fn load_archive(&self) -> Result<Store, StorageError> {
+        let _lock = self.storage.lock()?;
+        self.storage.load_archive()
+    }
+
+    /// Events logged after `since`
+    pub fn events(&self, since: u64) -> Result<Vec<Event>, StorageError> {
+        let _lock = self.storage.lock()?;
+        self.storage.events(since)
+    }
+
+    /// Reverts the last change, returning its name, or `None` if there is
+    /// nothing to undo
+    pub fn undo(&self) -> Result<Option<String>, StorageError> {
+        self.step(History::undo)
+    }
+
+    pub fn redo(&self) -> Result<Option<String>, StorageError> {
+        self.step(History::redo)
+    }
+
+    fn step(
+        &self,
+        apply: fn(&mut History, &mut Store, &mut Store) -> Option<String>,
+    ) -> Result<Option<String>, StorageError> {
+        let mut store = self.acquire(false)?;
+        let mut history = self.storage.load_history()?;
+        let mut archive = self.storage.load_archive()?;
+        let archived = archive.clone();
+
+        let Some(operation) = apply(&mut history, &mut store, &mut archive) else {
+            return Ok(None);
+        };
+        self.write(&store)?;
+        store.saved.set(true);
+        if archive != archived {
+            self.storage.save_archive(&archive)?;
+        }
+        self.storage.save_history(&history)?;
+
+        Ok(Some(operation))
+    }
+
+    fn write(&self, store: &Store) -> Result<(), StorageError> {
+        self.storage.save(store)?;
+        *self.stamp.lock().unwrap() = self.storage.stamp();
+
+        Ok(())
+    }
+}
+
+impl Deref for StoreGuard<'_> {
+    type Target = Store;
+
+    fn deref(&self) -> &Store {
+        &self.store
+    }
+}
+
+impl DerefMut for StoreGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Store {
+        &mut self.store
+    }
+}
+
+impl Drop for StoreGuard<'_> {
+    fn drop(&mut self) {
+        if !self.saved.get() {
+            *self.store = std::mem::take(&mut self.before);
+        }
+    }
+}
@@ -1,33 +1,153 @@
+use super::projects::ProjectName;
 use super::state::AppState;
-use crate::shared::file;
-use crate::shared::todo::{Todo, TodoList};
+use crate::shared::filter::Filter;
+use crate::shared::output::TodoResponse;
+use crate::shared::recurrence::Recurrence;
+use crate::shared::search::{FieldMatch, Query, SearchMode};
+use crate::shared::todo::{
+    Priority, SortBy, Status, Todo, TodoList, blocked_message, normalize_tag,
+};
 use actix_web::{HttpResponse, Responder, delete, get, post, put, web};
+use chrono::{Local, NaiveDate};
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(reset)
         .service(list)
+        .service(search)
+        .service(children)
         .service(create)
+        .service(batch)
         .service(update)
         .service(mark_done)
+        .service(set_status)
         .service(undo_done)
-        .service(delete);
+        .service(delete)
+        .service(add_tags)
+        .service(remove_tag)
+        .service(add_blockers)
+        .service(remove_blocker)
+        .service(set_recurrence)
+        .service(stop_recurrence)
+        .service(move_todo);
 }
 
 #[derive(serde::Deserialize)]
 pub struct CreateTodo {
     title: String,
+    #[serde(default)]
+    due: Option<NaiveDate>,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    notes: String,
+    parent_id: Option<u64>,
+    #[serde(default)]
+    blocked_by: Vec<u64>,
+    recurrence: Option<Recurrence>,
 }
 
+/// Fields left out are kept as they are
 #[derive(serde::Deserialize)]
 pub struct UpdateTodo {
-    pub title: String,
+    pub title: Option<String>,
+    /// `Some(None)` when `due` is `null`, which clears the due date
+    #[serde(default, deserialize_with = "present")]
+    pub due: Option<Option<NaiveDate>>,
+    pub priority: Option<Priority>,
+    pub notes: Option<String>,
+}
+
+// Tells a field sent as `null` apart from one left out, which `default` makes `None`
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    <Option<T> as serde::Deserialize>::deserialize(deserializer).map(Some)
+}
+
+#[derive(serde::Deserialize)]
+pub struct RecurrencePayload {
+    pub rule: Recurrence,
+}
+
+#[derive(serde::Deserialize)]
+pub struct BlockersPayload {
+    pub ids: Vec<u64>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TagsPayload {
+    pub tags: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct StatusPayload {
+    pub status: Status,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BatchAction {
+    MarkDone,
+    UndoDone,
+    Delete,
+}
+
+/// Applied to all of `ids` or, if any of them fails, to none
+#[derive(serde::Deserialize)]
+pub struct BatchRequest {
+    pub action: BatchAction,
+    pub ids: Vec<u64>,
+    /// As for `mark-done`, see `MarkQuery`
+    #[serde(default)]
+    pub cascade: bool,
+    #[serde(default)]
+    pub force: bool,
 }
 
 #[derive(serde::Serialize)]
-pub struct TodoResponse {
+pub struct BatchResponse {
+    /// Every todo that changed, including subtasks changed along with the
+    /// requested ones
+    pub changed: Vec<u64>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct MarkQuery {
+    /// Also mark open subtasks done instead of refusing
+    #[serde(default)]
+    pub cascade: bool,
+    /// Complete the todo and its subtasks even though they still have open
+    /// blockers
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct MoveTodo {
+    pub project: String,
+}
+
+// Structs rather than bare values so the same handlers also work under
+// `/projects/{project}`, where the path carries an extra segment
+#[derive(serde::Deserialize)]
+pub struct TodoPath {
+    pub id: u64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TagPath {
+    pub id: u64,
+    pub tag: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct BlockerPath {
     pub id: u64,
-    pub title: String,
-    pub done: bool,
+    pub blocker: u64,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -36,12 +156,39 @@ pub enum ListMode {
     All,
     Todo,
     Done,
+    Overdue,
+    Today,
+    Upcoming,
+    Blocked,
+    Ready,
+    Recurring,
 }
 
 #[derive(serde::Deserialize)]
 pub struct ListQuery {
     #[serde(default = "default_mode")]
     pub mode: ListMode,
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub sort: SortBy,
+    pub status: Option<Status>,
+    pub tag: Option<String>,
+    /// Filter expression, see `shared::filter`
+    pub filter: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    #[serde(default)]
+    pub mode: SearchMode,
+}
+
+#[derive(serde::Serialize)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    pub todo: TodoResponse,
+    pub matches: Vec<FieldMatch>,
 }
 
 fn default_mode() -> ListMode {
@@ -49,39 +196,201 @@ fn default_mode() -> ListMode {
 }
 
 #[get("/todos")]
-async fn list(state: web::Data<AppState>, query: web::Query<ListQuery>) -> HttpResponse {
-    let todos = state.lock().unwrap();
+async fn list(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    query: web::Query<ListQuery>,
+) -> HttpResponse {
+    let store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+    let today = Local::now().date_naive();
+    let filter = match query.filter.as_deref().map(|x| Filter::parse(x, today)) {
+        Some(Err(e)) => return HttpResponse::BadRequest().body(format!("Invalid filter: {}", e)),
+        Some(Ok(filter)) => Some(filter),
+        None => None,
+    };
 
-    let items: Vec<&Todo> = match query.mode {
+    let mut items: Vec<&Todo> = match query.mode {
         ListMode::All => todos.list().iter().collect(),
         ListMode::Todo => todos.todo().collect(),
         ListMode::Done => todos.done().collect(),
+        ListMode::Overdue => todos.overdue(today).collect(),
+        ListMode::Today => todos.due_today(today).collect(),
+        ListMode::Upcoming => todos.upcoming(today).collect(),
+        ListMode::Blocked => todos.blocked().collect(),
+        ListMode::Ready => todos.ready().collect(),
+        ListMode::Recurring => todos.recurring().collect(),
     };
 
+    if let Some(priority) = query.priority {
+        items.retain(|x| x.priority == priority);
+    }
+    if let Some(status) = query.status {
+        items.retain(|x| x.status == status);
+    }
+    if let Some(tag) = &query.tag {
+        items.retain(|x| x.has_tag(tag));
+    }
+    if let Some(filter) = &filter {
+        items.retain(|x| filter.matches(x, today));
+    }
+    query.sort.sort(&mut items);
+
     let response: Vec<TodoResponse> = items.iter().map(|t| TodoResponse::from(*t)).collect();
 
     HttpResponse::Ok().json(response)
 }
 
+#[get("/todos/search")]
+async fn search(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    query: web::Query<SearchQuery>,
+) -> HttpResponse {
+    let pattern = match Query::new(&query.q, query.mode) {
+        Ok(pattern) => pattern,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    let response: Vec<SearchResult> = crate::shared::search::search(todos, &pattern)
+        .into_iter()
+        .map(|(todo, matches)| SearchResult {
+            todo: TodoResponse::from(todo),
+            matches,
+        })
+        .collect();
+
+    HttpResponse::Ok().json(response)
+}
+
+#[get("/todos/{id}/children")]
+async fn children(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<TodoPath>,
+) -> HttpResponse {
+    let store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    if todos.get(path.id).is_none() {
+        return HttpResponse::NotFound().body(format!("Todo {} not found", path.id));
+    }
+
+    let response: Vec<TodoResponse> = todos.children(path.id).map(TodoResponse::from).collect();
+
+    HttpResponse::Ok().json(response)
+}
+
 #[post("/todos")]
-async fn create(state: web::Data<AppState>, payload: web::Json<CreateTodo>) -> HttpResponse {
-    let mut todos = state.lock().unwrap();
-    let todo = todos.add(payload.title.clone());
-    file::save_todos(&todos);
+async fn create(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    payload: web::Json<CreateTodo>,
+) -> HttpResponse {
+    if let Some(e) = payload.tags.iter().find_map(|x| normalize_tag(x).err()) {
+        return HttpResponse::BadRequest().body(e);
+    }
+
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+    let id = match payload.parent_id {
+        Some(parent) => match todos.add_child(parent, payload.title.clone()) {
+            Ok(todo) => todo.id,
+            Err(e) => return HttpResponse::BadRequest().body(e),
+        },
+        None => todos.add(payload.title.clone()).id,
+    };
+
+    if let Err(e) = todos
+        .set_due(id, payload.due)
+        .and_then(|_| todos.set_priority(id, payload.priority))
+        .and_then(|_| todos.set_notes(id, &payload.notes))
+        .and_then(|_| todos.set_recurrence(id, payload.recurrence.clone()))
+        .and_then(|_| {
+            payload
+                .tags
+                .iter()
+                .try_for_each(|x| todos.add_tag(id, x).map(|_| ()))
+        })
+        .and_then(|_| {
+            payload
+                .blocked_by
+                .iter()
+                .try_for_each(|x| todos.add_blocker(id, *x).map(|_| ()))
+        })
+    {
+        return HttpResponse::BadRequest().body(e);
+    }
+
+    let todo = todos.get(id).map(TodoResponse::from);
+    if let Err(e) = state.save(&store) {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
     HttpResponse::Created().json(todo)
 }
 
 #[put("/todos/{id}")]
 async fn update(
     state: web::Data<AppState>,
-    id: web::Path<u64>,
+    project: ProjectName,
+    path: web::Path<TodoPath>,
     body: web::Json<UpdateTodo>,
 ) -> impl Responder {
-    let mut todos = state.lock().unwrap();
+    let id = path.id;
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    if todos.get(id).is_none() {
+        return HttpResponse::NotFound().body(format!("Todo {} not found", id));
+    }
 
-    match todos.update_title(*id, &body.title) {
+    match body
+        .title
+        .as_ref()
+        .map_or(Ok(()), |x| todos.update_title(id, x))
+        .and_then(|_| body.due.map_or(Ok(()), |x| todos.set_due(id, x)))
+        .and_then(|_| body.priority.map_or(Ok(()), |x| todos.set_priority(id, x)))
+        .and_then(|_| {
+            body.notes
+                .as_ref()
+                .map_or(Ok(()), |x| todos.set_notes(id, x))
+        }) {
         Ok(_) => {
-            file::save_todos(&todos);
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
             HttpResponse::Ok().finish()
         }
         Err(e) => HttpResponse::NotFound().body(e),
@@ -89,25 +398,202 @@ async fn update(
 }
 
 #[post("/todos/{id}/mark-done")]
-async fn mark_done(state: web::Data<AppState>, id: web::Path<u64>) -> impl Responder {
-    let mut todos = state.lock().unwrap();
+async fn mark_done(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<TodoPath>,
+    query: web::Query<MarkQuery>,
+) -> impl Responder {
+    let id = path.id;
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    if todos.get(id).is_none() {
+        return HttpResponse::NotFound().body(format!("Todo {} not found", id));
+    }
 
-    match todos.mark(*id, true) {
+    let blockers = todos.open_blockers(id);
+    if !blockers.is_empty() && !query.force {
+        return HttpResponse::Conflict().body(format!(
+            "{}, pass force=true to mark it done anyway",
+            blocked_message(id, &blockers)
+        ));
+    }
+
+    let open = todos.open_descendants(id);
+    if !open.is_empty() && !query.cascade {
+        return HttpResponse::Conflict().body(format!(
+            "Todo {} has {} open subtasks, pass cascade=true to mark them done",
+            id,
+            open.len()
+        ));
+    }
+
+    // The todo's own blockers were checked above, those of its subtasks
+    // are checked here unless forced
+    let result = if query.force {
+        todos.force_mark_tree(id, true)
+    } else {
+        todos.mark_tree(id, true)
+    };
+    match result {
         Ok(_) => {
-            file::save_todos(&todos);
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
             HttpResponse::Ok().finish()
         }
-        Err(e) => HttpResponse::NotFound().body(e),
+        Err(e) => HttpResponse::Conflict().body(e),
+    }
+}
+
+#[post("/todos/batch")]
+async fn batch(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    body: web::Json<BatchRequest>,
+) -> impl Responder {
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    if let Some(id) = body.ids.iter().find(|x| todos.get(**x).is_none()) {
+        return HttpResponse::NotFound().body(format!("Todo {} not found", id));
+    }
+
+    // Nothing is saved unless every id worked, the guard rolls back the rest
+    let mut changed = Vec::new();
+    match body.action {
+        BatchAction::MarkDone => {
+            let pending: Vec<u64> = body
+                .ids
+                .iter()
+                .copied()
+                .filter(|x| todos.get(*x).is_some_and(|x| !x.is_done()))
+                .collect();
+            for &id in &pending {
+                let open = todos.open_descendants(id);
+                if open.iter().any(|x| !body.ids.contains(x)) && !body.cascade {
+                    return HttpResponse::Conflict().body(format!(
+                        "Todo {} has {} open subtasks, pass cascade=true to mark them done",
+                        id,
+                        open.len()
+                    ));
+                }
+            }
+
+            // Blockers completed in the same batch, directly or as a
+            // subtask, don't count
+            let result = if body.force {
+                todos.force_mark_trees(&pending, true)
+            } else {
+                todos.mark_trees(&pending, true)
+            };
+            match result {
+                Ok(ids) => changed = ids,
+                Err(e) => return HttpResponse::Conflict().body(e),
+            }
+        }
+        BatchAction::UndoDone => {
+            for &id in &body.ids {
+                if todos.get(id).is_some_and(|x| !x.is_done()) {
+                    continue;
+                }
+                changed.push(id);
+                if let Err(e) = todos.mark(id, false) {
+                    return HttpResponse::Conflict().body(e);
+                }
+            }
+        }
+        BatchAction::Delete => {
+            for &id in &body.ids {
+                // Already trashed along with a parent earlier in the batch
+                if todos.get(id).is_none() {
+                    continue;
+                }
+                changed.push(id);
+                changed.extend(todos.descendants(id));
+                if let Err(e) = todos.remove(id) {
+                    return HttpResponse::Conflict().body(e);
+                }
+            }
+        }
+    }
+
+    if let Err(e) = state.save(&store) {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+
+    changed.sort_unstable();
+    changed.dedup();
+    HttpResponse::Ok().json(BatchResponse { changed })
+}
+
+#[post("/todos/{id}/status")]
+async fn set_status(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<TodoPath>,
+    body: web::Json<StatusPayload>,
+) -> impl Responder {
+    let id = path.id;
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    if todos.get(id).is_none() {
+        return HttpResponse::NotFound().body(format!("Todo {} not found", id));
+    }
+
+    match todos.set_status(id, body.status) {
+        Ok(_) => {
+            let todo = todos.get(id).map(TodoResponse::from);
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            HttpResponse::Ok().json(todo)
+        }
+        Err(e) => HttpResponse::Conflict().body(e),
     }
 }
 
 #[post("/todos/{id}/undo-done")]
-async fn undo_done(state: web::Data<AppState>, id: web::Path<u64>) -> impl Responder {
-    let mut todos = state.lock().unwrap();
+async fn undo_done(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<TodoPath>,
+) -> impl Responder {
+    let id = path.id;
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
 
-    match todos.mark(*id, false) {
+    match todos.mark(id, false) {
         Ok(_) => {
-            file::save_todos(&todos);
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
             HttpResponse::Ok().finish()
         }
         Err(e) => HttpResponse::NotFound().body(e),
@@ -115,34 +601,263 @@ async fn undo_done(state: web::Data<AppState>, id: web::Path<u64>) -> impl Respo
 }
 
 #[delete("/todos/{id}")]
-async fn delete(state: web::Data<AppState>, id: web::Path<u64>) -> impl Responder {
-    let mut todos = state.lock().unwrap();
+async fn delete(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<TodoPath>,
+) -> impl Responder {
+    let id = path.id;
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
 
-    match todos.remove(*id) {
+    match todos.remove(id) {
         Ok(_) => {
-            file::save_todos(&todos);
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
             HttpResponse::NoContent().finish()
         }
         Err(e) => HttpResponse::NotFound().body(e),
     }
 }
 
-#[delete("/todos/reset")]
-async fn reset(state: web::Data<AppState>) -> impl Responder {
-    let mut todos = state.lock().unwrap();
-    *todos = TodoList::new();
+#[post("/todos/{id}/tags")]
+async fn add_tags(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<TodoPath>,
+    body: web::Json<TagsPayload>,
+) -> impl Responder {
+    let id = path.id;
 
-    let _ = file::save_todos(&todos);
+    if let Some(e) = body.tags.iter().find_map(|x| normalize_tag(x).err()) {
+        return HttpResponse::BadRequest().body(e);
+    }
 
-    HttpResponse::Ok().body("Reset")
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    match body
+        .tags
+        .iter()
+        .try_for_each(|x| todos.add_tag(id, x).map(|_| ()))
+    {
+        Ok(_) => {
+            let todo = todos.get(id).map(TodoResponse::from);
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            HttpResponse::Ok().json(todo)
+        }
+        Err(e) => HttpResponse::NotFound().body(e),
+    }
+}
+
+#[delete("/todos/{id}/tags/{tag}")]
+async fn remove_tag(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<TagPath>,
+) -> impl Responder {
+    let TagPath { id, tag } = path.into_inner();
+
+    if let Err(e) = normalize_tag(&tag) {
+        return HttpResponse::BadRequest().body(e);
+    }
+
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    match todos.remove_tag(id, &tag) {
+        Ok(_) => {
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => HttpResponse::NotFound().body(e),
+    }
+}
+
+#[post("/todos/{id}/blocked-by")]
+async fn add_blockers(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<TodoPath>,
+    body: web::Json<BlockersPayload>,
+) -> impl Responder {
+    let id = path.id;
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    if todos.get(id).is_none() {
+        return HttpResponse::NotFound().body(format!("Todo {} not found", id));
+    }
+
+    match body
+        .ids
+        .iter()
+        .try_for_each(|x| todos.add_blocker(id, *x).map(|_| ()))
+    {
+        Ok(_) => {
+            let todo = todos.get(id).map(TodoResponse::from);
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            HttpResponse::Ok().json(todo)
+        }
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+#[delete("/todos/{id}/blocked-by/{blocker}")]
+async fn remove_blocker(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<BlockerPath>,
+) -> impl Responder {
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    match todos.remove_blocker(path.id, path.blocker) {
+        Ok(_) => {
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => HttpResponse::NotFound().body(e),
+    }
+}
+
+#[put("/todos/{id}/recurrence")]
+async fn set_recurrence(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<TodoPath>,
+    body: web::Json<RecurrencePayload>,
+) -> impl Responder {
+    let id = path.id;
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    match todos.set_recurrence(id, Some(body.rule.clone())) {
+        Ok(_) => {
+            let todo = todos.get(id).map(TodoResponse::from);
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            HttpResponse::Ok().json(todo)
+        }
+        Err(e) => HttpResponse::NotFound().body(e),
+    }
+}
+
+#[delete("/todos/{id}/recurrence")]
+async fn stop_recurrence(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<TodoPath>,
+) -> impl Responder {
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    match todos.set_recurrence(path.id, None) {
+        Ok(_) => {
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => HttpResponse::NotFound().body(e),
+    }
 }
 
-impl From<&Todo> for TodoResponse {
-    fn from(t: &Todo) -> Self {
-        Self {
-            id: t.id,
-            title: t.title.clone(),
-            done: t.done,
+#[post("/todos/{id}/move")]
+async fn move_todo(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<TodoPath>,
+    body: web::Json<MoveTodo>,
+) -> impl Responder {
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+
+    match store.move_todo(&project.0, path.id, &body.project) {
+        Ok(new_id) => {
+            let todo = store
+                .project(&body.project)
+                .ok()
+                .and_then(|x| x.get(new_id))
+                .map(TodoResponse::from);
+            if let Err(e) = state.save(&store) {
+                return HttpResponse::InternalServerError().body(e.to_string());
+            }
+            HttpResponse::Ok().json(todo)
         }
+        Err(e) => HttpResponse::NotFound().body(e),
+    }
+}
+
+#[delete("/todos/reset")]
+async fn reset(state: web::Data<AppState>, project: ProjectName) -> impl Responder {
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+    *todos = TodoList::new();
+
+    if let Err(e) = state.save(&store) {
+        return HttpResponse::InternalServerError().body(e.to_string());
     }
+
+    HttpResponse::Ok().body("Reset")
 }
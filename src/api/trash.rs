@@ -0,0 +1,86 @@
+use super::projects::ProjectName;
+use super::state::AppState;
+use super::todos::TodoPath;
+use crate::shared::output::TodoResponse;
+use actix_web::{HttpResponse, Responder, delete, get, post, web};
+use chrono::{DateTime, Utc};
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list).service(restore).service(purge);
+}
+
+#[derive(serde::Serialize)]
+struct TrashedResponse {
+    #[serde(flatten)]
+    todo: TodoResponse,
+    deleted_at: DateTime<Utc>,
+}
+
+#[get("/trash")]
+async fn list(state: web::Data<AppState>, project: ProjectName) -> impl Responder {
+    let store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    let body: Vec<TrashedResponse> = todos
+        .trash
+        .iter()
+        .map(|x| TrashedResponse {
+            todo: TodoResponse::from(&x.todo),
+            deleted_at: x.deleted_at,
+        })
+        .collect();
+    HttpResponse::Ok().json(body)
+}
+
+#[post("/trash/{id}/restore")]
+async fn restore(
+    state: web::Data<AppState>,
+    project: ProjectName,
+    path: web::Path<TodoPath>,
+) -> impl Responder {
+    let id = path.id;
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    if let Err(e) = todos.restore(id) {
+        return HttpResponse::NotFound().body(e);
+    }
+    let body = todos.get(id).map(TodoResponse::from);
+    if let Err(e) = state.save(&store) {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+
+    HttpResponse::Ok().json(body)
+}
+
+/// Empties the trash for good, regardless of the retention period
+#[delete("/trash")]
+async fn purge(state: web::Data<AppState>, project: ProjectName) -> impl Responder {
+    let mut store = match state.lock() {
+        Ok(store) => store,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let todos = match store.project_mut(&project.0) {
+        Ok(todos) => todos,
+        Err(e) => return HttpResponse::NotFound().body(e),
+    };
+
+    todos.purge(None);
+    if let Err(e) = state.save(&store) {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+
+    HttpResponse::NoContent().finish()
+}
@@ -1,12 +1,31 @@
 use actix_web::{App, HttpServer, web};
-use std::sync::{Arc, Mutex};
+use clap::Parser;
+use std::path::PathBuf;
 use todo::api;
-use todo::shared::file;
+use todo::api::state::AppState;
+use todo::shared::config::Config;
+use todo::shared::storage;
+
+#[derive(Parser)]
+#[command(name = "todo-api")]
+#[command(about = "A simple todo list api", long_about = None)]
+struct Args {
+    /// Todo file to use instead of the configured one
+    #[arg(long)]
+    file: Option<PathBuf>,
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let todos = file::load_todos();
-    let state = web::Data::new(Arc::new(Mutex::new(todos)));
+    let args = Args::parse();
+    let config = Config::load().map_err(std::io::Error::other)?;
+    let path = config
+        .store_path(args.file)
+        .map_err(std::io::Error::other)?;
+    let storage = storage::open(config.backend, path).map_err(std::io::Error::other)?;
+
+    let todos = storage.load().map_err(std::io::Error::other)?;
+    let state = web::Data::new(AppState::new(todos, storage, config));
 
     HttpServer::new(move || App::new().app_data(state.clone()).configure(api::routes))
         .bind(("127.0.0.1", 8080))?
@@ -1,15 +1,123 @@
 use clap::Parser;
-use todo::cli::{Cli, RunResult, run};
-use todo::prompter::DialoguerPrompter;
-use todo::shared::file::{load_todos, save_todos};
+use todo::cli::{
+    Cli, CliCommand, Command, RunResult, archive, doctor, execute, import_json, migrate, print_log,
+    redo, undo,
+};
+use todo::prompter::{AssumeYes, DialoguerPrompter, Prompter};
+use todo::shared::config::Config;
+use todo::shared::history;
+use todo::shared::storage::{self, Backend};
+use todo::shared::store::Store;
 
 fn main() -> Result<(), String> {
     let cli = Cli::parse();
+    let config = Config::load()?;
+    let path = config.store_path(cli.file.clone())?;
+    let storage = storage::open(config.backend, path).map_err(|e| e.to_string())?;
+    let prompter: &dyn Prompter = if cli.yes {
+        &AssumeYes(&DialoguerPrompter)
+    } else {
+        &DialoguerPrompter
+    };
+
+    match &cli.command {
+        CliCommand::Doctor | CliCommand::Migrate { .. } if config.backend != Backend::Json => {
+            return Err(String::from(
+                "Only JSON todo files can be repaired or migrated",
+            ));
+        }
+        CliCommand::Doctor => {
+            let _lock = storage.lock().map_err(|e| e.to_string())?;
+            return doctor(storage.path(), prompter).map(|_| ());
+        }
+        CliCommand::Migrate { dry_run } => {
+            let _lock = storage.lock().map_err(|e| e.to_string())?;
+            return migrate(storage.path(), *dry_run).map(|_| ());
+        }
+        CliCommand::Log { since } => {
+            let _lock = storage.lock().map_err(|e| e.to_string())?;
+            print_log(&storage.events(*since).map_err(|e| e.to_string())?);
+            return Ok(());
+        }
+        CliCommand::ImportJson { source } => {
+            let _lock = storage.lock().map_err(|e| e.to_string())?;
+            return import_json(source, storage.as_ref(), prompter).map(|_| ());
+        }
+        CliCommand::Archive { before } => {
+            let _lock = storage.lock().map_err(|e| e.to_string())?;
+            return archive(storage.as_ref(), &cli.project, *before).map(|_| ());
+        }
+        CliCommand::Todo(Command::List { archived: true, .. }) => {
+            let mut archived = {
+                let _lock = storage.lock().map_err(|e| e.to_string())?;
+                storage.load_archive().map_err(|e| e.to_string())?
+            };
+            // Projects show up in the archive once something was archived
+            if archived.project(&cli.project).is_err() {
+                archived.create(&cli.project)?;
+            }
+            return execute(cli, &mut archived, prompter).map(|_| ());
+        }
+        _ => {}
+    }
 
-    let mut todo = load_todos();
+    // A corrupt file stops here instead of being overwritten by the next save
+    let (mut store, mut history, mut archive, loaded) = {
+        let _lock = storage.lock().map_err(|e| e.to_string())?;
+        let mut store = storage
+            .load()
+            .map_err(|e| format!("{}, run `todo-cli doctor`", e))?;
+        let stepping = matches!(cli.command, CliCommand::Undo | CliCommand::Redo);
+        // Archiving first would record a change, which undo would then revert
+        // instead of the one asked for
+        if !stepping && let Some(cutoff) = config.archive_cutoff() {
+            storage
+                .archive(&mut store, None, Some(cutoff))
+                .map_err(|e| e.to_string())?;
+        }
+        let history = storage.load_history().map_err(|e| e.to_string())?;
+        // Undoing or redoing archiving moves todos in or out of the archive
+        let archive = if stepping {
+            storage.load_archive().map_err(|e| e.to_string())?
+        } else {
+            Store::default()
+        };
+        (store, history, archive, storage.stamp())
+    };
+    let archived = archive.clone();
+    // Saved along with whatever the command changes
+    if let Some(cutoff) = config.trash_cutoff() {
+        store.purge_trash(cutoff);
+    }
+    let before = store.clone();
+
+    let result = match cli.command {
+        CliCommand::Undo => undo(&mut store, &mut archive, &mut history),
+        CliCommand::Redo => redo(&mut store, &mut archive, &mut history),
+        _ => {
+            let result = execute(cli, &mut store, prompter)?;
+            if result == RunResult::Changed && store != before {
+                history.record(history::describe(&before, &store), &before, &store);
+            }
+            result
+        }
+    };
 
-    if let RunResult::Changed = run(cli.command, &mut todo, &DialoguerPrompter)? {
-        save_todos(&todo);
+    // The lock is not held while prompting, so that todo-api is not stalled
+    // waiting for input; a write made in the meantime is detected instead
+    if let RunResult::Changed = result {
+        let _lock = storage.lock().map_err(|e| e.to_string())?;
+        if storage.stamp() != loaded {
+            return Err(format!(
+                "{} was changed by another process, nothing was saved",
+                storage.path().display()
+            ));
+        }
+        storage.save(&store).map_err(|e| e.to_string())?;
+        if archive != archived {
+            storage.save_archive(&archive).map_err(|e| e.to_string())?;
+        }
+        storage.save_history(&history).map_err(|e| e.to_string())?;
     };
 
     Ok(())
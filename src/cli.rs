@@ -1,13 +1,96 @@
 use crate::prompter::Prompter;
-use crate::shared::todo::{Todo, TodoList};
+use crate::shared::edit::TodoEdit;
+use crate::shared::events::Event;
+use crate::shared::file;
+use crate::shared::filter::Filter;
+use crate::shared::history::History;
+use crate::shared::migrate;
+use crate::shared::output::{self, Format};
+use crate::shared::recurrence::Recurrence;
+use crate::shared::search::{self, Field, Query, SearchMode};
+use crate::shared::storage::{JsonStorage, Storage};
+use crate::shared::store::{DEFAULT_PROJECT, Store};
+use crate::shared::todo::{Priority, SortBy, Status, Todo, TodoList, Trashed, start_of_day};
+use chrono::{DateTime, Local, NaiveDate, Utc};
 use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 #[derive(Parser)]
 #[command(name = "todo")]
 #[command(about = "A simple todo list cli", long_about = None)]
 pub struct Cli {
+    /// Project to work on
+    #[arg(long, global = true, default_value = DEFAULT_PROJECT)]
+    pub project: String,
+    /// Todo file to use instead of the configured one
+    #[arg(long, global = true)]
+    pub file: Option<PathBuf>,
+    /// Answer yes to every confirmation, for scripts
+    #[arg(long, global = true)]
+    pub yes: bool,
     #[command(subcommand)]
-    pub command: Command,
+    pub command: CliCommand,
+}
+
+#[derive(Subcommand)]
+pub enum CliCommand {
+    #[command(flatten)]
+    Todo(Command),
+    /// Manage projects
+    Project {
+        #[command(subcommand)]
+        command: ProjectCommand,
+    },
+    /// Check the todo file and repair it or restore it from backup
+    Doctor,
+    /// Copy a JSON todo file into the configured storage, e.g. to move to SQLite
+    ImportJson { source: PathBuf },
+    /// Show the log of changes made to the store
+    Log {
+        /// Only show events after this sequence number
+        #[arg(long, default_value_t = 0)]
+        since: u64,
+    },
+    /// Revert the last change
+    Undo,
+    /// Apply the last undone change again
+    Redo,
+    /// Upgrade the todo file to the current schema version
+    Migrate {
+        /// Only show the steps that would run
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Move done todos out of the project into the archive
+    Archive {
+        /// Only archive todos completed before this date, in YYYY-MM-DD format
+        #[arg(long)]
+        before: Option<NaiveDate>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProjectCommand {
+    Create {
+        name: String,
+    },
+    Rename {
+        from: String,
+        to: String,
+    },
+    List,
+    Delete {
+        name: String,
+    },
+    /// Move a todo from the current project into another one
+    Move {
+        to: String,
+    },
 }
 
 #[derive(ValueEnum, Clone, Debug)]
@@ -15,15 +98,44 @@ pub enum ListMode {
     All,
     Done,
     Todo,
+    Overdue,
+    Today,
+    Upcoming,
+    Blocked,
+    Ready,
+    Recurring,
 }
 
 #[derive(Subcommand)]
 pub enum Command {
     Add {
         key: String,
+        /// Due date in YYYY-MM-DD format
+        #[arg(long)]
+        due: Option<NaiveDate>,
+        #[arg(long, value_enum, default_value_t = Priority::None)]
+        priority: Priority,
+        /// Tag to attach, may be repeated
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        #[arg(long)]
+        notes: Option<String>,
+        /// Recurrence rule, e.g. daily, weekly, "every 2 weeks" or "every mon,thu"
+        #[arg(long)]
+        repeat: Option<Recurrence>,
+    },
+    /// Add a subtask under a selected todo
+    AddChild {
+        key: String,
+    },
+    /// Mark todos done, e.g. `3,5,7-10`, or pick them when no ids are given
+    MarkDone {
+        ids: Vec<IdList>,
+    },
+    /// Reopen done todos, or pick them when no ids are given
+    UndoDone {
+        ids: Vec<IdList>,
     },
-    MarkDone,
-    UndoDone,
     List {
         #[arg(
             long,
@@ -31,9 +143,124 @@ pub enum Command {
             default_value_t = ListMode::All,
         )]
         mode: ListMode,
+        /// Only show todos with this priority
+        #[arg(long, value_enum)]
+        priority: Option<Priority>,
+        #[arg(long, value_enum, default_value_t = SortBy::Priority)]
+        sort: SortBy,
+        /// Only show todos with this status
+        #[arg(long, value_enum)]
+        status: Option<Status>,
+        /// Only show todos with this tag, may be repeated
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Only show todos matching an expression, e.g. `status:open due<friday -tag:someday`
+        #[arg(long)]
+        filter: Option<String>,
+        /// List archived todos instead
+        #[arg(long)]
+        archived: bool,
+        /// Print the todos for other programs instead of as sections
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+    },
+    /// Move todos to the trash, or pick them when no ids are given
+    Delete {
+        ids: Vec<IdList>,
+    },
+    /// Change the title of a todo, prompting for whatever is not given
+    Update {
+        id: Option<u64>,
+        #[arg(long)]
+        title: Option<String>,
+    },
+    Prioritize,
+    Tag {
+        #[arg(required = true)]
+        tags: Vec<String>,
     },
-    Delete,
-    Update,
+    Untag {
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Mark a selected todo as blocked by the given todo ids
+    Block {
+        #[arg(required = true)]
+        blockers: Vec<u64>,
+    },
+    /// Remove the given todo ids from the blockers of a selected todo
+    Unblock {
+        #[arg(required = true)]
+        blockers: Vec<u64>,
+    },
+    /// Make a selected todo repeat, e.g. daily, weekly, "every 2 weeks" or "every mon,thu"
+    Repeat {
+        rule: Recurrence,
+    },
+    /// Stop a recurring todo from repeating
+    StopRepeat,
+    /// Change the status of a selected todo
+    Status,
+    /// Print the full record of a todo
+    Show,
+    /// Edit a todo in $EDITOR
+    Edit,
+    /// Find todos by title, notes or tags
+    Search {
+        query: String,
+        #[arg(long, value_enum, default_value_t = SearchMode::Substring)]
+        mode: SearchMode,
+        /// Print the todos for other programs instead of highlighting matches
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+    },
+    /// List deleted todos
+    Trash,
+    /// Bring a deleted todo back from the trash
+    Restore,
+    /// Permanently delete everything in the trash
+    Purge,
+}
+
+/// Todo ids given as a comma separated list of ids and ranges, e.g. `3,5,7-10`
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdList(pub Vec<u64>);
+
+/// Most ids a single range like `7-10` may expand to
+pub const MAX_ID_RANGE: u64 = 10_000;
+
+impl FromStr for IdList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ids = Vec::new();
+
+        for part in s.split(',').map(str::trim) {
+            let id = |x: &str| {
+                x.trim()
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid todo id '{}'", part))
+            };
+            match part.split_once('-') {
+                Some((from, to)) => {
+                    let (from, to) = (id(from)?, id(to)?);
+                    if from > to {
+                        return Err(format!("Invalid id range '{}'", part));
+                    }
+                    if to - from >= MAX_ID_RANGE {
+                        return Err(format!(
+                            "Id range '{}' is longer than {} ids",
+                            part, MAX_ID_RANGE
+                        ));
+                    }
+                    ids.extend(from..=to);
+                }
+                None => ids.push(id(part)?),
+            }
+        }
+
+        Ok(IdList(ids))
+    }
 }
 
 #[derive(ValueEnum, Clone, Debug, PartialEq)]
@@ -42,153 +269,1183 @@ pub enum RunResult {
     Changed,
 }
 
+impl From<bool> for RunResult {
+    fn from(changed: bool) -> Self {
+        if changed {
+            RunResult::Changed
+        } else {
+            RunResult::NoChange
+        }
+    }
+}
+
+pub fn execute(cli: Cli, store: &mut Store, prompter: &dyn Prompter) -> Result<RunResult, String> {
+    match cli.command {
+        CliCommand::Todo(command) => run(command, store.project_mut(&cli.project)?, prompter),
+        CliCommand::Project { command } => run_project(command, store, &cli.project, prompter),
+        CliCommand::Doctor
+        | CliCommand::ImportJson { .. }
+        | CliCommand::Migrate { .. }
+        | CliCommand::Archive { .. }
+        | CliCommand::Log { .. }
+        | CliCommand::Undo
+        | CliCommand::Redo => Err(String::from(
+            "This command must run before the store is loaded",
+        )),
+    }
+}
+
+pub fn print_log(events: &[Event]) {
+    if events.is_empty() {
+        println!("No events");
+    }
+
+    for event in events {
+        println!(
+            "#{} {} {}: {}",
+            event.seq,
+            fmt_time(Some(event.at)),
+            event.actor,
+            event.describe()
+        );
+    }
+}
+
+pub fn undo(store: &mut Store, archive: &mut Store, history: &mut History) -> RunResult {
+    match history.undo(store, archive) {
+        Some(operation) => {
+            println!("Undid {}", operation);
+            RunResult::Changed
+        }
+        None => {
+            println!("Nothing to undo");
+            RunResult::NoChange
+        }
+    }
+}
+
+pub fn redo(store: &mut Store, archive: &mut Store, history: &mut History) -> RunResult {
+    match history.redo(store, archive) {
+        Some(operation) => {
+            println!("Redid {}", operation);
+            RunResult::Changed
+        }
+        None => {
+            println!("Nothing to redo");
+            RunResult::NoChange
+        }
+    }
+}
+
+/// Checks the event log and then the todo file, offering to repair either
+pub fn doctor(path: &Path, prompter: &dyn Prompter) -> Result<RunResult, String> {
+    // The log comes first, repairing the store replays it
+    let log = file::events_path(path);
+    let events = match file::load_recent_events(path, 0) {
+        Ok(_) => {
+            if log.exists() {
+                println!("{} is healthy", log.display());
+            }
+            RunResult::NoChange
+        }
+        Err(e) => {
+            println!("{}", e);
+            repair_events(path, prompter)?
+        }
+    };
+
+    let store = match file::load_todos(path) {
+        Ok(_) => {
+            println!("{} is healthy", path.display());
+            RunResult::NoChange
+        }
+        Err(e) => {
+            println!("{}", e);
+            repair_todos(path, prompter)?
+        }
+    };
+
+    if store == RunResult::Changed || events == RunResult::Changed {
+        Ok(RunResult::Changed)
+    } else {
+        Ok(RunResult::NoChange)
+    }
+}
+
+fn repair_todos(path: &Path, prompter: &dyn Prompter) -> Result<RunResult, String> {
+    let backup = file::backup_path(path);
+    let (store, prompt) = if let Some((store, dropped)) = file::salvage(path) {
+        let prompt = format!("Rebuild the file dropping {} unreadable todos?", dropped);
+        (store, prompt)
+    } else if let Some(store) = backup
+        .exists()
+        .then(|| file::load_todos(&backup).ok())
+        .flatten()
+    {
+        (store, format!("Restore from {}?", backup.display()))
+    } else {
+        return Err(String::from(
+            "The file cannot be repaired and has no usable backup",
+        ));
+    };
+
+    // The backup can be a snapshot or more behind, so everything logged
+    // since is replayed, rotated events included
+    let events = file::load_events(path, store.seq()).map_err(|e| e.to_string())?;
+    let prompt = if events.is_empty() {
+        prompt
+    } else {
+        format!(
+            "{} {} logged changes are replayed on top.",
+            prompt,
+            events.len()
+        )
+    };
+
+    if !prompter.confirm(&prompt)? {
+        println!("Action cancelled");
+        return Ok(RunResult::NoChange);
+    }
+
+    let mut store = store;
+    for event in &events {
+        event.apply(&mut store);
+    }
+    let corrupt = file::replace_corrupt(path, &store).map_err(|e| e.to_string())?;
+    println!(
+        "Repaired, the damaged file was kept as {}",
+        corrupt.display()
+    );
+    Ok(RunResult::Changed)
+}
+
+// Either drops just the unreadable lines, or everything from the first of
+// them on, which is safer when later events depend on a lost one
+fn repair_events(path: &Path, prompter: &dyn Prompter) -> Result<RunResult, String> {
+    let file::SalvagedEvents { events, unreadable } =
+        file::salvage_events(path).map_err(|e| e.to_string())?;
+    let Some(&first) = unreadable.first() else {
+        return Ok(RunResult::NoChange);
+    };
+    let kept = events.iter().filter(|(line, _)| *line < first).count();
+
+    let options = vec![
+        format!("Drop the {} unreadable lines", unreadable.len()),
+        format!(
+            "Truncate the log at line {}, dropping {} more events",
+            first,
+            events.len() - kept
+        ),
+    ];
+    let Some(choice) = prompter.select(&options, "Repair the event log")? else {
+        println!("Action cancelled");
+        return Ok(RunResult::NoChange);
+    };
+
+    let events: Vec<Event> = events
+        .into_iter()
+        .filter(|(line, _)| choice == 0 || *line < first)
+        .map(|(_, event)| event)
+        .collect();
+    let corrupt = file::replace_corrupt_events(path, &events).map_err(|e| e.to_string())?;
+    println!(
+        "Repaired, the damaged log was kept as {}",
+        corrupt.display()
+    );
+    Ok(RunResult::Changed)
+}
+
+pub fn migrate(path: &Path, dry_run: bool) -> Result<RunResult, String> {
+    if !path.exists() {
+        println!("{} does not exist yet, nothing to migrate", path.display());
+        return Ok(RunResult::NoChange);
+    }
+
+    let data = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut value: Value = serde_json::from_str(&data).map_err(|e| {
+        format!(
+            "{} is corrupt: {}, run `todo-cli doctor`",
+            path.display(),
+            e
+        )
+    })?;
+    let version = migrate::version_of(&value);
+    let applied = migrate::upgrade(&mut value)?;
+
+    if applied.is_empty() {
+        println!("{} is at schema version {}", path.display(), version);
+        return Ok(RunResult::NoChange);
+    }
+
+    for (step, changes) in &applied {
+        println!(
+            "v{} -> v{}: {} ({} changes)",
+            step.from,
+            step.from + 1,
+            step.description,
+            changes
+        );
+    }
+
+    if dry_run {
+        println!("Dry run, nothing was written");
+        return Ok(RunResult::NoChange);
+    }
+
+    let store = file::load_todos(path).map_err(|e| e.to_string())?;
+    file::save_todos(path, &store).map_err(|e| e.to_string())?;
+    println!(
+        "Upgraded to schema version {}, the old file was kept as {}",
+        migrate::SCHEMA_VERSION,
+        file::upgrade_backup_path(path, version).display()
+    );
+    Ok(RunResult::Changed)
+}
+
+pub fn import_json(
+    source: &Path,
+    storage: &dyn Storage,
+    prompter: &dyn Prompter,
+) -> Result<RunResult, String> {
+    // Through `JsonStorage` so that changes logged after its snapshot count
+    let source_storage = JsonStorage {
+        path: source.to_path_buf(),
+    };
+    let store = source_storage.load().map_err(|e| e.to_string())?;
+    let existing = storage.load().map_err(|e| e.to_string())?;
+
+    let count = |store: &Store| -> usize { store.projects().map(|(_, x)| x.items.len()).sum() };
+    if count(&existing) > 0 {
+        let prompt = format!(
+            "Replace the {} todos in {}?",
+            count(&existing),
+            storage.path().display()
+        );
+        if !prompter.confirm(&prompt)? {
+            println!("Action cancelled");
+            return Ok(RunResult::NoChange);
+        }
+    }
+
+    storage.save(&store).map_err(|e| e.to_string())?;
+    println!(
+        "Copied {} todos from {} into {}",
+        count(&store),
+        source.display(),
+        storage.path().display()
+    );
+    Ok(RunResult::Changed)
+}
+
+/// Archives the done todos of `project`, see `Storage::archive`
+pub fn archive(
+    storage: &dyn Storage,
+    project: &str,
+    before: Option<NaiveDate>,
+) -> Result<RunResult, String> {
+    let mut store = storage.load().map_err(|e| e.to_string())?;
+    store.project(project)?;
+
+    let count = storage
+        .archive(&mut store, Some(project), before.map(start_of_day))
+        .map_err(|e| e.to_string())?;
+
+    if count == 0 {
+        println!("Nothing to archive");
+        return Ok(RunResult::NoChange);
+    }
+    println!(
+        "Archived {} todo{}",
+        count,
+        if count == 1 { "" } else { "s" }
+    );
+    Ok(RunResult::Changed)
+}
+
 pub fn run(
     command: Command,
     todo: &mut TodoList,
     prompter: &dyn Prompter,
 ) -> Result<RunResult, String> {
     match command {
-        Command::Add { key } => {
-            todo.add(key);
+        Command::Add {
+            key,
+            due,
+            priority,
+            tags,
+            notes,
+            repeat,
+        } => {
+            let id = todo.add(key).id;
+            todo.set_due(id, due)?;
+            todo.set_priority(id, priority)?;
+            for tag in &tags {
+                todo.add_tag(id, tag)?;
+            }
+            if let Some(notes) = notes {
+                todo.set_notes(id, &notes)?;
+            }
+            todo.set_recurrence(id, repeat)?;
             Ok(RunResult::Changed)
         }
-        Command::MarkDone => {
+        Command::AddChild { key } => {
+            let items = todo.list();
+
+            if items.is_empty() {
+                println!("No todos to add a subtask to");
+                return Ok(RunResult::NoChange);
+            }
+
+            let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
+
+            let Some(selection) = prompter.select(&labels, "Select parent todo")? else {
+                println!("Action cancelled");
+                return Ok(RunResult::NoChange);
+            };
+
+            let parent = items[selection].id;
+            let child = todo.add_child(parent, key)?;
+
+            println!("Added '{}' under [{}]", child.fmt(), parent);
+            Ok(RunResult::Changed)
+        }
+        Command::MarkDone { ids } => {
             let items: Vec<&Todo> = todo.todo().collect();
+            let ids = pick(
+                todo,
+                ids,
+                &items,
+                "Select todos to mark as done",
+                "No todos to mark as done",
+                prompter,
+            )?;
+
+            let mut changed = false;
+            for id in ids {
+                changed |= mark_done(todo, id, prompter)?;
+            }
+
+            Ok(changed.into())
+        }
+        Command::UndoDone { ids } => {
+            let items: Vec<&Todo> = todo.done().collect();
+            let ids = pick(
+                todo,
+                ids,
+                &items,
+                "Select completed todos to undo done",
+                "No todos to undo done",
+                prompter,
+            )?;
+
+            let mut changed = false;
+            for id in ids {
+                if todo.get(id).is_some_and(|x| !x.is_done()) {
+                    println!("Todo [{}] is not done", id);
+                    continue;
+                }
+                todo.mark(id, false)?;
+                changed = true;
+            }
+
+            Ok(changed.into())
+        }
+        Command::List {
+            mode,
+            priority,
+            sort,
+            status,
+            tags,
+            filter,
+            // todo-cli lists the archive's copy of the project instead
+            archived: _,
+            format,
+        } => {
+            let today = Local::now().date_naive();
+            let filter = filter
+                .map(|x| Filter::parse(&x, today))
+                .transpose()
+                .map_err(|e| format!("Invalid filter: {}", e))?;
+            let view = View {
+                priority,
+                sort,
+                status,
+                tags,
+                filter,
+                today,
+            };
+
+            let sections = match mode {
+                ListMode::All => vec![
+                    ("# TODO", view.apply(todo.todo())),
+                    ("# DONE", view.apply(todo.done())),
+                ],
+                ListMode::Done => vec![("# DONE", view.apply(todo.done()))],
+                ListMode::Todo => vec![("# TODO", view.apply(todo.todo()))],
+                ListMode::Overdue => vec![("# OVERDUE", view.apply(todo.overdue(today)))],
+                ListMode::Today => vec![("# TODAY", view.apply(todo.due_today(today)))],
+                ListMode::Upcoming => vec![("# UPCOMING", view.apply(todo.upcoming(today)))],
+                ListMode::Blocked => vec![("# BLOCKED", view.apply(todo.blocked()))],
+                ListMode::Ready => vec![("# READY", view.apply(todo.ready()))],
+                ListMode::Recurring => vec![("# RECURRING", view.apply(todo.recurring()))],
+            };
+
+            if let Some(format) = format {
+                let items: Vec<&Todo> = sections.into_iter().flat_map(|(_, x)| x).collect();
+                print!("{}", output::render(format, &items));
+                return Ok(RunResult::NoChange);
+            }
+            for (index, (header, items)) in sections.into_iter().enumerate() {
+                if index > 0 {
+                    println!();
+                }
+                print_section(header, items);
+            }
+
+            Ok(RunResult::NoChange)
+        }
+        Command::Update { id, title } => {
+            let id = match id {
+                Some(id) => id,
+                None => {
+                    let items = todo.list();
+
+                    if items.is_empty() {
+                        println!("No todos to update");
+                        return Ok(RunResult::NoChange);
+                    }
+
+                    let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
+
+                    let Some(selection) = prompter.select(&labels, "Select toddo to update")?
+                    else {
+                        println!("Action cancelled");
+                        return Ok(RunResult::NoChange);
+                    };
+                    items[selection].id
+                }
+            };
+
+            let old_title = todo
+                .get(id)
+                .map(|x| x.title.clone())
+                .ok_or_else(|| format!("Todo '{}' is not found.", id))?;
+            let new_title = match title {
+                Some(title) => title,
+                None => {
+                    let Some(title) = prompter.input("Edit title", &old_title)? else {
+                        println!("Action cancelled");
+                        return Ok(RunResult::NoChange);
+                    };
+                    title
+                }
+            };
+
+            if new_title.trim() == old_title {
+                println!("Title unchanged");
+                return Ok(RunResult::NoChange);
+            }
+
+            todo.update_title(id, &new_title)?;
+
+            println!("Todo '[{}] {}' updated to '{}'", id, old_title, new_title);
+            Ok(RunResult::Changed)
+        }
+        Command::Delete { ids } => {
+            let items: Vec<&Todo> = todo.list().iter().collect();
+            let ids = pick(
+                todo,
+                ids,
+                &items,
+                "Select todos to delete",
+                "No todos to delete",
+                prompter,
+            )?;
+
+            let mut changed = false;
+            for id in ids {
+                changed |= delete(todo, id, prompter)?;
+            }
+
+            Ok(changed.into())
+        }
+        Command::Prioritize => {
+            let items = todo.list();
 
             if items.is_empty() {
-                println!("No todos to mark as done");
+                println!("No todos to prioritize");
                 return Ok(RunResult::NoChange);
             }
 
             let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
 
-            let Some(selection) = prompter.select(&labels, "Select a todo to mark as done")? else {
+            let Some(selection) = prompter.select(&labels, "Select todo to prioritize")? else {
                 println!("Action cancelled");
                 return Ok(RunResult::NoChange);
             };
 
             let id = items[selection].id;
-            todo.mark(id, true)?;
+            let title = items[selection].title.clone();
+
+            let priorities = Priority::value_variants();
+            let labels: Vec<String> = priorities.iter().map(|x| x.to_string()).collect();
 
+            let Some(selection) = prompter.select(&labels, "Select priority")? else {
+                println!("Action cancelled");
+                return Ok(RunResult::NoChange);
+            };
+
+            let priority = priorities[selection];
+            todo.set_priority(id, priority)?;
+
+            println!("Todo '[{}] {}' set to {} priority", id, title, priority);
             Ok(RunResult::Changed)
         }
-        Command::UndoDone => {
-            let items: Vec<&Todo> = todo.done().collect();
+        Command::Tag { tags } => {
+            let items = todo.list();
+
+            if items.is_empty() {
+                println!("No todos to tag");
+                return Ok(RunResult::NoChange);
+            }
+
+            let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
+
+            let Some(selection) = prompter.select(&labels, "Select todo to tag")? else {
+                println!("Action cancelled");
+                return Ok(RunResult::NoChange);
+            };
+
+            let id = items[selection].id;
+            let mut changed = false;
+            for tag in &tags {
+                changed |= todo.add_tag(id, tag)?;
+            }
+
+            if !changed {
+                println!("Tags unchanged");
+                return Ok(RunResult::NoChange);
+            }
+
+            println!("Tagged todo {} with {}", id, tags.join(", "));
+            Ok(RunResult::Changed)
+        }
+        Command::Untag { tags } => {
+            let items: Vec<&Todo> = todo.list().iter().filter(|x| !x.tags.is_empty()).collect();
 
             if items.is_empty() {
-                println!("No todos to undo done");
+                println!("No tagged todos");
                 return Ok(RunResult::NoChange);
             }
 
             let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
 
-            let Some(selection) = prompter.select(&labels, "Select completed todo to undo done")?
-            else {
+            let Some(selection) = prompter.select(&labels, "Select todo to untag")? else {
                 println!("Action cancelled");
                 return Ok(RunResult::NoChange);
             };
 
             let id = items[selection].id;
-            todo.mark(id, false)?;
+            let mut changed = false;
+            for tag in &tags {
+                changed |= todo.remove_tag(id, tag)?;
+            }
+
+            if !changed {
+                println!("Tags unchanged");
+                return Ok(RunResult::NoChange);
+            }
+
+            println!("Removed {} from todo {}", tags.join(", "), id);
             Ok(RunResult::Changed)
         }
-        Command::List { mode } => {
+        Command::Block { blockers } => {
             let items = todo.list();
 
-            match mode {
-                ListMode::All => {
-                    println!("# TODO");
-                    items
-                        .iter()
-                        .filter(|x| !x.done)
-                        .for_each(|x| println!("[{}] {}", x.id, x.title));
-                    println!();
+            if items.is_empty() {
+                println!("No todos to block");
+                return Ok(RunResult::NoChange);
+            }
 
-                    println!("# DONE");
-                    items
-                        .iter()
-                        .filter(|x| x.done)
-                        .for_each(|x| println!("[{}] {}", x.id, x.title));
-                }
-                ListMode::Done => {
-                    println!("# DONE");
-                    items
-                        .iter()
-                        .filter(|x| x.done)
-                        .for_each(|x| println!("[{}] {}", x.id, x.title));
-                }
-                ListMode::Todo => {
-                    println!("# TODO");
-                    items
-                        .iter()
-                        .filter(|x| !x.done)
-                        .for_each(|x| println!("[{}] {}", x.id, x.title));
-                }
+            let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
+
+            let Some(selection) = prompter.select(&labels, "Select blocked todo")? else {
+                println!("Action cancelled");
+                return Ok(RunResult::NoChange);
             };
 
-            Ok(RunResult::NoChange)
+            let id = items[selection].id;
+            let mut changed = false;
+            for blocker in &blockers {
+                changed |= todo.add_blocker(id, *blocker)?;
+            }
+
+            if !changed {
+                println!("Blockers unchanged");
+                return Ok(RunResult::NoChange);
+            }
+
+            println!("Todo {} is now blocked by {:?}", id, blockers);
+            Ok(RunResult::Changed)
+        }
+        Command::Unblock { blockers } => {
+            let items: Vec<&Todo> = todo
+                .list()
+                .iter()
+                .filter(|x| !x.blocked_by.is_empty())
+                .collect();
+
+            if items.is_empty() {
+                println!("No blocked todos");
+                return Ok(RunResult::NoChange);
+            }
+
+            let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
+
+            let Some(selection) = prompter.select(&labels, "Select todo to unblock")? else {
+                println!("Action cancelled");
+                return Ok(RunResult::NoChange);
+            };
+
+            let id = items[selection].id;
+            let mut changed = false;
+            for blocker in &blockers {
+                changed |= todo.remove_blocker(id, *blocker)?;
+            }
+
+            if !changed {
+                println!("Blockers unchanged");
+                return Ok(RunResult::NoChange);
+            }
+
+            println!("Removed blockers {:?} from todo {}", blockers, id);
+            Ok(RunResult::Changed)
+        }
+        Command::Repeat { rule } => {
+            let items: Vec<&Todo> = todo.todo().collect();
+
+            if items.is_empty() {
+                println!("No todos to repeat");
+                return Ok(RunResult::NoChange);
+            }
+
+            let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
+
+            let Some(selection) = prompter.select(&labels, "Select todo to repeat")? else {
+                println!("Action cancelled");
+                return Ok(RunResult::NoChange);
+            };
+
+            let id = items[selection].id;
+            let title = items[selection].title.clone();
+            todo.set_recurrence(id, Some(rule.clone()))?;
+
+            println!("Todo '[{}] {}' repeats {}", id, title, rule);
+            Ok(RunResult::Changed)
+        }
+        Command::StopRepeat => {
+            let items: Vec<&Todo> = todo.recurring().collect();
+
+            if items.is_empty() {
+                println!("No recurring todos");
+                return Ok(RunResult::NoChange);
+            }
+
+            let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
+
+            let Some(selection) = prompter.select(&labels, "Select todo to stop repeating")? else {
+                println!("Action cancelled");
+                return Ok(RunResult::NoChange);
+            };
+
+            let id = items[selection].id;
+            let title = items[selection].title.clone();
+            todo.set_recurrence(id, None)?;
+
+            println!("Todo '[{}] {}' no longer repeats", id, title);
+            Ok(RunResult::Changed)
         }
-        Command::Update => {
+        Command::Status => {
             let items = todo.list();
 
             if items.is_empty() {
-                println!("No todos to update");
+                println!("No todos to change");
                 return Ok(RunResult::NoChange);
             }
 
             let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
 
-            let Some(selection) = prompter.select(&labels, "Select toddo to update")? else {
+            let Some(selection) = prompter.select(&labels, "Select todo to change status")? else {
                 println!("Action cancelled");
                 return Ok(RunResult::NoChange);
             };
 
             let id = items[selection].id;
             let title = items[selection].title.clone();
-            let Some(new_title) = prompter.input("Edit title", &title)? else {
+            let current = items[selection].status;
+
+            let statuses = Status::value_variants();
+            let labels: Vec<String> = statuses.iter().map(|x| x.to_string()).collect();
+
+            let Some(selection) = prompter.select(&labels, "Select status")? else {
                 println!("Action cancelled");
                 return Ok(RunResult::NoChange);
             };
 
-            if new_title.trim() == title {
-                println!("Title unchanged");
+            let status = statuses[selection];
+            if status == current {
+                println!("Status unchanged");
                 return Ok(RunResult::NoChange);
             }
 
-            todo.update_title(id, &new_title)?;
+            // Done confirms open blockers and subtasks like mark-done
+            if status == Status::Done {
+                if !mark_done(todo, id, prompter)? {
+                    return Ok(RunResult::NoChange);
+                }
+            } else {
+                todo.set_status(id, status)?;
+            }
 
-            println!("Todo '[{}] {}' updated to '{}'", id, title, new_title);
+            println!("Todo '[{}] {}' is now {}", id, title, status);
             Ok(RunResult::Changed)
         }
-        Command::Delete => {
+        Command::Show => {
             let items = todo.list();
 
             if items.is_empty() {
-                println!("No todos to delete");
+                println!("No todos to show");
                 return Ok(RunResult::NoChange);
             }
 
             let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
 
-            let Some(selection) = prompter.select(&labels, "Select todo to delete")? else {
+            let Some(selection) = prompter.select(&labels, "Select todo to show")? else {
+                println!("Action cancelled");
+                return Ok(RunResult::NoChange);
+            };
+
+            print_details(&items[selection]);
+            Ok(RunResult::NoChange)
+        }
+        Command::Edit => {
+            let items = todo.list();
+
+            if items.is_empty() {
+                println!("No todos to edit");
+                return Ok(RunResult::NoChange);
+            }
+
+            let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
+
+            let Some(selection) = prompter.select(&labels, "Select todo to edit")? else {
                 println!("Action cancelled");
                 return Ok(RunResult::NoChange);
             };
 
             let id = items[selection].id;
-            let title = items[selection].title.clone();
+            let current = TodoEdit::from(&items[selection]);
+
+            let Some(buffer) = prompter.edit(&current.render())? else {
+                println!("Action cancelled");
+                return Ok(RunResult::NoChange);
+            };
+
+            let edited = TodoEdit::parse(&buffer)?;
+
+            if edited == current {
+                println!("Todo unchanged");
+                return Ok(RunResult::NoChange);
+            }
+
+            edited.apply(todo, id)?;
+
+            println!("Todo [{}] updated", id);
+            Ok(RunResult::Changed)
+        }
+        Command::Search {
+            query,
+            mode,
+            format,
+        } => {
+            let query = Query::new(&query, mode)?;
+            let hits = search::search(todo, &query);
+
+            if let Some(format) = format {
+                let items: Vec<&Todo> = hits.iter().map(|(x, _)| *x).collect();
+                print!("{}", output::render(format, &items));
+                return Ok(RunResult::NoChange);
+            }
+            if hits.is_empty() {
+                println!("No todos match '{}'", query.as_str());
+            }
+
+            let (start, end) = if io::stdout().is_terminal() {
+                ("\x1b[1;33m", "\x1b[0m")
+            } else {
+                ("", "")
+            };
+            for (todo, matches) in hits {
+                let title = matches
+                    .iter()
+                    .find(|x| x.field == Field::Title)
+                    .map(|x| search::highlight(&x.text, &x.spans, start, end))
+                    .unwrap_or_else(|| todo.title.clone());
+                println!("[{}] {}", todo.id, title);
+
+                for found in matches.iter().filter(|x| x.field != Field::Title) {
+                    let text = search::highlight(&found.text, &found.spans, start, end);
+                    match found.field {
+                        Field::Tag => println!("    tag: +{}", text),
+                        _ => println!("    notes: {}", text.replace('\n', " ")),
+                    }
+                }
+            }
+
+            Ok(RunResult::NoChange)
+        }
+        Command::Trash => {
+            if todo.trash.is_empty() {
+                println!("The trash is empty");
+            }
+
+            for entry in &todo.trash {
+                println!("{}", fmt_trashed(entry));
+            }
+
+            Ok(RunResult::NoChange)
+        }
+        Command::Restore => {
+            if todo.trash.is_empty() {
+                println!("No todos to restore");
+                return Ok(RunResult::NoChange);
+            }
 
-            let confirm = prompter
-                .confirm(format!("Are you sure you want to delete '{}'?", title).as_str())?;
+            let labels: Vec<String> = todo.trash.iter().map(fmt_trashed).collect();
+
+            let Some(selection) = prompter.select(&labels, "Select todo to restore")? else {
+                println!("Action cancelled");
+                return Ok(RunResult::NoChange);
+            };
+
+            let id = todo.trash[selection].todo.id;
+            todo.restore(id)?;
+
+            println!(
+                "Restored {}",
+                todo.get(id).map(|x| x.fmt()).unwrap_or_default()
+            );
+            Ok(RunResult::Changed)
+        }
+        Command::Purge => {
+            if todo.trash.is_empty() {
+                println!("The trash is empty");
+                return Ok(RunResult::NoChange);
+            }
+
+            let prompt = format!(
+                "Permanently delete {} todos in the trash?",
+                todo.trash.len()
+            );
+            if !prompter.confirm(&prompt)? {
+                println!("Purge cancelled");
+                return Ok(RunResult::NoChange);
+            }
+
+            let purged = todo.purge(None);
+            println!("Purged {} todos", purged);
+            Ok(RunResult::Changed)
+        }
+    }
+}
+
+pub fn run_project(
+    command: ProjectCommand,
+    store: &mut Store,
+    current: &str,
+    prompter: &dyn Prompter,
+) -> Result<RunResult, String> {
+    match command {
+        ProjectCommand::Create { name } => {
+            store.create(&name)?;
+            println!("Created project '{}'", name);
+            Ok(RunResult::Changed)
+        }
+        ProjectCommand::Rename { from, to } => {
+            store.rename(&from, &to)?;
+            println!("Renamed project '{}' to '{}'", from, to);
+            Ok(RunResult::Changed)
+        }
+        ProjectCommand::List => {
+            for name in store.names() {
+                let todos = store.project(name)?;
+                let marker = if name == current { "*" } else { " " };
+                println!(
+                    "{} {} ({} open, {} done)",
+                    marker,
+                    name,
+                    todos.todo().count(),
+                    todos.done().count()
+                );
+            }
+            Ok(RunResult::NoChange)
+        }
+        ProjectCommand::Delete { name } => {
+            let count = store.project(&name)?.list().len();
+
+            let confirm = prompter.confirm(
+                format!(
+                    "Are you sure you want to delete project '{}' and its {} todos?",
+                    name, count
+                )
+                .as_str(),
+            )?;
 
             if !confirm {
                 println!("Delete cancelled");
                 return Ok(RunResult::NoChange);
             }
 
-            todo.remove(id)?;
-            println!("Deleted '[{}] {}'", id, title);
+            store.delete(&name)?;
+            println!("Deleted project '{}'", name);
+            Ok(RunResult::Changed)
+        }
+        ProjectCommand::Move { to } => {
+            store.project_mut(&to)?;
+            let items = store.project_mut(current)?.list();
+
+            if items.is_empty() {
+                println!("No todos to move");
+                return Ok(RunResult::NoChange);
+            }
+
+            let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
+
+            let Some(selection) = prompter.select(&labels, "Select todo to move")? else {
+                println!("Action cancelled");
+                return Ok(RunResult::NoChange);
+            };
+
+            let id = items[selection].id;
+            let title = items[selection].title.clone();
+            let new_id = store.move_todo(current, id, &to)?;
+
+            println!("Moved '{}' to project '{}' as [{}]", title, to, new_id);
             Ok(RunResult::Changed)
         }
     }
 }
+
+struct View {
+    priority: Option<Priority>,
+    sort: SortBy,
+    status: Option<Status>,
+    tags: Vec<String>,
+    filter: Option<Filter>,
+    today: NaiveDate,
+}
+
+impl View {
+    fn apply<'a>(&self, items: impl Iterator<Item = &'a Todo>) -> Vec<&'a Todo> {
+        let mut items: Vec<&Todo> = items
+            .filter(|x| self.priority.is_none_or(|p| x.priority == p))
+            .filter(|x| self.status.is_none_or(|s| x.status == s))
+            .filter(|x| self.tags.iter().all(|tag| x.has_tag(tag)))
+            .filter(|x| {
+                self.filter
+                    .as_ref()
+                    .is_none_or(|f| f.matches(x, self.today))
+            })
+            .collect();
+        self.sort.sort(&mut items);
+        items
+    }
+}
+
+// Ids given on the command line, checked to exist, or the ones picked from
+// `items`; empty when there is nothing to pick or the pick was cancelled
+fn pick(
+    todo: &TodoList,
+    ids: Vec<IdList>,
+    items: &[&Todo],
+    prompt: &str,
+    empty: &str,
+    prompter: &dyn Prompter,
+) -> Result<Vec<u64>, String> {
+    let mut given: Vec<u64> = Vec::new();
+    for id in ids.into_iter().flat_map(|x| x.0) {
+        if todo.get(id).is_none() {
+            return Err(format!("Todo '{}' is not found.", id));
+        }
+        if !given.contains(&id) {
+            given.push(id);
+        }
+    }
+    if !given.is_empty() {
+        return Ok(given);
+    }
+
+    if items.is_empty() {
+        println!("{}", empty);
+        return Ok(Vec::new());
+    }
+
+    let labels: Vec<String> = items.iter().map(|x| x.fmt()).collect();
+    let selection = prompter.multi_select(&labels, prompt)?.unwrap_or_default();
+    if selection.is_empty() {
+        println!("Action cancelled");
+    }
+
+    Ok(selection.into_iter().map(|x| items[x].id).collect())
+}
+
+// Returns whether the todo was marked, confirming open blockers and subtasks
+fn mark_done(todo: &mut TodoList, id: u64, prompter: &dyn Prompter) -> Result<bool, String> {
+    let Some(item) = todo.get(id) else {
+        return Err(format!("Todo '{}' is not found.", id));
+    };
+    if item.is_done() {
+        println!("Todo [{}] is already done", id);
+        return Ok(false);
+    }
+
+    let title = item.title.clone();
+    let blockers = todo.open_blockers(id);
+
+    if !blockers.is_empty() {
+        let blockers: Vec<String> = blockers.iter().map(|x| x.to_string()).collect();
+        let confirm = prompter.confirm(
+            format!(
+                "'{}' is blocked by open todos {}. Mark it done anyway?",
+                title,
+                blockers.join(", ")
+            )
+            .as_str(),
+        )?;
+
+        if !confirm {
+            println!("Action cancelled");
+            return Ok(false);
+        }
+    }
+
+    let open = todo.open_descendants(id);
+
+    if !open.is_empty() {
+        let confirm = prompter.confirm(
+            format!(
+                "'{}' has {} open subtasks. Mark them done as well?",
+                title,
+                open.len()
+            )
+            .as_str(),
+        )?;
+
+        if !confirm {
+            println!("Action cancelled");
+            return Ok(false);
+        }
+    }
+
+    // Open blockers of the selected todo were confirmed above
+    todo.force_mark(id, true)?;
+    if !open.is_empty() {
+        todo.mark_tree(id, true)?;
+    }
+
+    Ok(true)
+}
+
+// Returns whether the todo was moved to the trash
+fn delete(todo: &mut TodoList, id: u64, prompter: &dyn Prompter) -> Result<bool, String> {
+    // Already trashed along with a parent deleted before it
+    let Some(item) = todo.get(id) else {
+        return Ok(false);
+    };
+
+    let title = item.title.clone();
+    let subtasks = todo.descendants(id).len();
+
+    let prompt = if subtasks == 0 {
+        format!("Are you sure you want to delete '{}'?", title)
+    } else {
+        format!(
+            "Are you sure you want to delete '{}' and its {} subtasks?",
+            title, subtasks
+        )
+    };
+    let confirm = prompter.confirm(prompt.as_str())?;
+
+    if !confirm {
+        println!("Delete cancelled");
+        return Ok(false);
+    }
+
+    todo.remove(id)?;
+    println!("Moved '[{}] {}' to the trash", id, title);
+    Ok(true)
+}
+
+fn print_details(todo: &Todo) {
+    let due = todo.due.map(|x| x.to_string()).unwrap_or_default();
+    let tags: Vec<String> = todo.tags.iter().map(|x| format!("+{}", x)).collect();
+
+    println!("[{}] {}", todo.id, todo.title);
+    println!("Status:   {}", todo.status);
+    println!("Due:      {}", due);
+    println!("Priority: {}", todo.priority);
+    println!("Tags:     {}", tags.join(" "));
+
+    if let Some(recurrence) = &todo.recurrence {
+        println!("Repeats:  {}", recurrence);
+    }
+
+    println!("Created:  {}", fmt_time(todo.created_at));
+    println!("Updated:  {}", fmt_time(todo.updated_at));
+    if todo.is_done() {
+        println!("Done:     {}", fmt_time(todo.completed_at));
+    }
+
+    if !todo.blocked_by.is_empty() {
+        let blockers: Vec<String> = todo.blocked_by.iter().map(|x| x.to_string()).collect();
+        println!("Blocked:  {}", blockers.join(", "));
+    }
+
+    if !todo.notes.is_empty() {
+        println!();
+        println!("{}", todo.notes);
+    }
+}
+
+fn fmt_trashed(entry: &Trashed) -> String {
+    format!(
+        "{} (deleted {})",
+        entry.todo.fmt(),
+        fmt_time(Some(entry.deleted_at))
+    )
+}
+
+fn fmt_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|x| x.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+// Subtasks are indented under their parent when both are in the section
+fn print_section(header: &str, items: Vec<&Todo>) {
+    println!("{}", header);
+
+    let ids: HashSet<u64> = items.iter().map(|x| x.id).collect();
+    items
+        .iter()
+        .filter(|x| x.parent.is_none_or(|p| !ids.contains(&p)))
+        .for_each(|x| print_tree(x, &items, 0));
+}
+
+fn print_tree(todo: &Todo, items: &[&Todo], depth: usize) {
+    println!("{}{}", "  ".repeat(depth), todo.fmt());
+
+    items
+        .iter()
+        .filter(|x| x.parent == Some(todo.id))
+        .for_each(|x| print_tree(x, items, depth + 1));
+}
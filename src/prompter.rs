@@ -1,11 +1,16 @@
-use dialoguer::{Confirm, Input, Select};
+use dialoguer::{Confirm, Editor, Input, MultiSelect, Select};
 
 pub trait Prompter {
     fn select(&self, items: &[String], prompt: &str) -> Result<Option<usize>, String>;
 
+    /// Indices of the chosen items, `None` or empty when nothing was chosen
+    fn multi_select(&self, items: &[String], prompt: &str) -> Result<Option<Vec<usize>>, String>;
+
     fn input(&self, prompt: &str, initial: &str) -> Result<Option<String>, String>;
 
     fn confirm(&self, prompt: &str) -> Result<bool, String>;
+
+    fn edit(&self, text: &str) -> Result<Option<String>, String>;
 }
 
 pub struct DialoguerPrompter;
@@ -20,6 +25,14 @@ impl Prompter for DialoguerPrompter {
             .map_err(|e| e.to_string())
     }
 
+    fn multi_select(&self, items: &[String], prompt: &str) -> Result<Option<Vec<usize>>, String> {
+        MultiSelect::new()
+            .with_prompt(prompt)
+            .items(items)
+            .interact_opt()
+            .map_err(|e| e.to_string())
+    }
+
     fn input(&self, prompt: &str, initial: &str) -> Result<Option<String>, String> {
         Input::<String>::new()
             .with_prompt(prompt)
@@ -36,4 +49,37 @@ impl Prompter for DialoguerPrompter {
             .interact()
             .map_err(|e| e.to_string())
     }
+
+    fn edit(&self, text: &str) -> Result<Option<String>, String> {
+        Editor::new()
+            .extension(".txt")
+            .edit(text)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Passes everything through to another prompter but answers yes to every
+/// confirmation, used for `--yes`
+pub struct AssumeYes<'a>(pub &'a dyn Prompter);
+
+impl Prompter for AssumeYes<'_> {
+    fn select(&self, items: &[String], prompt: &str) -> Result<Option<usize>, String> {
+        self.0.select(items, prompt)
+    }
+
+    fn multi_select(&self, items: &[String], prompt: &str) -> Result<Option<Vec<usize>>, String> {
+        self.0.multi_select(items, prompt)
+    }
+
+    fn input(&self, prompt: &str, initial: &str) -> Result<Option<String>, String> {
+        self.0.input(prompt, initial)
+    }
+
+    fn confirm(&self, _: &str) -> Result<bool, String> {
+        Ok(true)
+    }
+
+    fn edit(&self, text: &str) -> Result<Option<String>, String> {
+        self.0.edit(text)
+    }
 }
@@ -0,0 +1,82 @@
+use super::storage::Backend;
+use chrono::{DateTime, Days, Utc};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const CONFIG_FILE: &str = "config.json";
+const APP_DIR: &str = "todo";
+
+/// Settings read from `$XDG_CONFIG_HOME/todo/config.json`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub file: Option<PathBuf>,
+    pub backend: Backend,
+    /// Days deleted todos stay in the trash, 0 keeps them forever
+    pub trash_retention_days: u32,
+    /// Done todos are archived this many days after completion, 0 never does
+    pub archive_after_days: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            file: None,
+            backend: Backend::default(),
+            trash_retention_days: 30,
+            archive_after_days: 0,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Config, String> {
+        let Some(path) = config_dir().map(|x| x.join(APP_DIR).join(CONFIG_FILE)) else {
+            return Ok(Config::default());
+        };
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let data = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&data).map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+    }
+
+    /// Todos deleted before this are purged from the trash
+    pub fn trash_cutoff(&self) -> Option<DateTime<Utc>> {
+        (self.trash_retention_days > 0)
+            .then(|| Utc::now() - Days::new(self.trash_retention_days as u64))
+    }
+
+    /// Todos completed before this are moved to the archive
+    pub fn archive_cutoff(&self) -> Option<DateTime<Utc>> {
+        (self.archive_after_days > 0)
+            .then(|| Utc::now() - Days::new(self.archive_after_days as u64))
+    }
+
+    /// Store location, taken from the `--file` flag, then `TODO_FILE`, then
+    /// the config file, then the XDG data directory
+    pub fn store_path(&self, flag: Option<PathBuf>) -> Result<PathBuf, String> {
+        flag.or_else(|| env_path("TODO_FILE"))
+            .or_else(|| self.file.clone())
+            .or_else(|| data_dir().map(|x| x.join(APP_DIR).join(self.backend.default_file())))
+            .ok_or_else(|| String::from("Cannot find a home directory, use --file or TODO_FILE"))
+    }
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    env::var_os(name)
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+}
+
+fn config_dir() -> Option<PathBuf> {
+    env_path("XDG_CONFIG_HOME").or_else(|| env_path("HOME").map(|x| x.join(".config")))
+}
+
+fn data_dir() -> Option<PathBuf> {
+    env_path("XDG_DATA_HOME").or_else(|| env_path("HOME").map(|x| x.join(".local/share")))
+}
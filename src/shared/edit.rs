@@ -0,0 +1,148 @@
+use super::recurrence::Recurrence;
+use super::todo::{Priority, Todo, TodoList, normalize_tag};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use std::collections::BTreeSet;
+
+const NOTES_SEPARATOR: &str = "---";
+
+/// Editable fields of a todo, rendered as a text buffer of `key: value`
+/// headers followed by a `---` line and free-form notes
+#[derive(Debug, Clone, PartialEq)]
+pub struct TodoEdit {
+    pub title: String,
+    pub due: Option<NaiveDate>,
+    pub priority: Priority,
+    pub tags: BTreeSet<String>,
+    pub repeat: Option<Recurrence>,
+    pub notes: String,
+}
+
+impl TodoEdit {
+    pub fn render(&self) -> String {
+        let due = self.due.map(|x| x.to_string()).unwrap_or_default();
+        let tags: Vec<String> = self.tags.iter().map(|x| format!("+{}", x)).collect();
+        let repeat = self
+            .repeat
+            .as_ref()
+            .map(|x| x.to_string())
+            .unwrap_or_default();
+
+        let mut buffer = format!(
+            "title: {}\ndue: {}\npriority: {}\ntags: {}\nrepeat: {}\n{}\n",
+            self.title,
+            due,
+            self.priority,
+            tags.join(" "),
+            repeat,
+            NOTES_SEPARATOR
+        );
+        if !self.notes.is_empty() {
+            buffer.push_str(&self.notes);
+            buffer.push('\n');
+        }
+
+        buffer
+    }
+
+    pub fn parse(buffer: &str) -> Result<TodoEdit, String> {
+        let mut edit = TodoEdit {
+            title: String::new(),
+            due: None,
+            priority: Priority::None,
+            tags: BTreeSet::new(),
+            repeat: None,
+            notes: String::new(),
+        };
+        let mut lines = buffer.lines().enumerate();
+
+        for (index, line) in lines.by_ref() {
+            if line.trim() == NOTES_SEPARATOR {
+                break;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("Line {}: expected 'key: value'", index + 1))?;
+            let value = value.trim();
+
+            match key.trim() {
+                "title" => edit.title = value.to_string(),
+                "due" if value.is_empty() => edit.due = None,
+                "due" => {
+                    let due = value
+                        .parse()
+                        .map_err(|_| format!("Line {}: invalid due date '{}'", index + 1, value))?;
+                    edit.due = Some(due);
+                }
+                "priority" if value.is_empty() => edit.priority = Priority::None,
+                "priority" => {
+                    edit.priority = Priority::from_str(value, true)
+                        .map_err(|_| format!("Line {}: invalid priority '{}'", index + 1, value))?
+                }
+                "tags" => {
+                    edit.tags = value
+                        .split_whitespace()
+                        .map(normalize_tag)
+                        .collect::<Result<_, _>>()?
+                }
+                "repeat" if value.is_empty() => edit.repeat = None,
+                "repeat" => {
+                    edit.repeat = Some(
+                        value
+                            .parse()
+                            .map_err(|e| format!("Line {}: {}", index + 1, e))?,
+                    )
+                }
+                other => return Err(format!("Line {}: unknown field '{}'", index + 1, other)),
+            }
+        }
+
+        if edit.title.is_empty() {
+            return Err(String::from("Title must not be empty"));
+        }
+
+        let notes: Vec<&str> = lines.map(|(_, line)| line).collect();
+        edit.notes = notes.join("\n").trim().to_string();
+
+        Ok(edit)
+    }
+
+    pub fn apply(&self, todos: &mut TodoList, id: u64) -> Result<(), String> {
+        let current = todos
+            .get(id)
+            .map(TodoEdit::from)
+            .ok_or_else(|| format!("Todo {} not found", id))?;
+
+        todos.update_title(id, &self.title)?;
+        todos.set_due(id, self.due)?;
+        todos.set_priority(id, self.priority)?;
+        todos.set_notes(id, &self.notes)?;
+        todos.set_recurrence(id, self.repeat.clone())?;
+
+        for tag in current.tags.difference(&self.tags) {
+            todos.remove_tag(id, tag)?;
+        }
+        for tag in self.tags.difference(&current.tags) {
+            todos.add_tag(id, tag)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&Todo> for TodoEdit {
+    fn from(todo: &Todo) -> Self {
+        TodoEdit {
+            title: todo.title.clone(),
+            due: todo.due,
+            priority: todo.priority,
+            tags: todo.tags.clone(),
+            repeat: todo.recurrence.clone(),
+            notes: todo.notes.clone(),
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use super::migrate;
+use super::store::{DEFAULT_PROJECT, Store};
+use super::todo::{Status, Todo, TodoList, Trashed};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::path::Path;
+
+/// The store is rewritten as a snapshot every this many events; loading
+/// replays whatever was logged after the snapshot
+pub const SNAPSHOT_INTERVAL: u64 = 50;
+
+/// One entry of the append-only event log kept next to the store
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    /// User and program that made the change, e.g. `alice via todo-cli`
+    pub actor: String,
+    pub project: String,
+    /// The project's next id once the change is made, so that replaying it
+    /// cannot hand out an id again. Missing in events logged before it was
+    /// recorded and for deleted projects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_id: Option<u64>,
+    /// Schema of the todos the event carries, see `migrate::upgrade_change`
+    #[serde(default = "first_change_version")]
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+fn first_change_version() -> u32 {
+    migrate::FIRST_CHANGE_VERSION
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Change {
+    /// A new todo, as it was created
+    Add {
+        todo: Todo,
+    },
+    /// A todo whose status alone changed, e.g. one marked done
+    Status {
+        todo: Todo,
+        from: Status,
+    },
+    /// A todo whose title alone changed
+    Rename {
+        todo: Todo,
+        from: String,
+    },
+    /// Any other edit to an existing todo, carrying its new state and the
+    /// fields that changed
+    Update {
+        todo: Todo,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        fields: Vec<String>,
+    },
+    /// A todo removed for good, e.g. moved to another project
+    Remove {
+        id: u64,
+        title: String,
+    },
+    /// A todo moved to the trash
+    Trash {
+        entry: Trashed,
+    },
+    Restore {
+        todo: Todo,
+    },
+    Purge {
+        id: u64,
+        title: String,
+    },
+    /// The project's list was emptied and its ids start over
+    Reset,
+    CreateProject,
+    DeleteProject,
+}
+
+impl Change {
+    /// e.g. `add [3] Buy milk`, or `add work/[3] Buy milk` outside the
+    /// default project
+    pub fn describe(&self, project: &str) -> String {
+        let label = |id: u64, title: &str| {
+            if project == DEFAULT_PROJECT {
+                format!("[{}] {}", id, title)
+            } else {
+                format!("{}/[{}] {}", project, id, title)
+            }
+        };
+
+        match self {
+            Change::Add { todo } => format!("add {}", label(todo.id, &todo.title)),
+            Change::Status { todo, from } => {
+                let label = label(todo.id, &todo.title);
+                match todo.status {
+                    Status::Done => format!("complete {}", label),
+                    Status::Cancelled => format!("cancel {}", label),
+                    _ if matches!(from, Status::Done | Status::Cancelled) => {
+                        format!("reopen {}", label)
+                    }
+                    status => format!("set {} to {}", label, status),
+                }
+            }
+            Change::Rename { todo, from } => {
+                format!("rename {} to {}", label(todo.id, from), todo.title)
+            }
+            Change::Update { todo, fields } if fields.is_empty() => {
+                format!("update {}", label(todo.id, &todo.title))
+            }
+            Change::Update { todo, fields } => format!(
+                "update {} ({})",
+                label(todo.id, &todo.title),
+                fields.join(", ")
+            ),
+            Change::Remove { id, title } => format!("remove {}", label(*id, title)),
+            Change::Trash { entry } => {
+                format!("delete {}", label(entry.todo.id, &entry.todo.title))
+            }
+            Change::Restore { todo } => format!("restore {}", label(todo.id, &todo.title)),
+            Change::Purge { id, title } => format!("purge {}", label(*id, title)),
+            Change::Reset => format!("reset project {}", project),
+            Change::CreateProject => format!("create project {}", project),
+            Change::DeleteProject => format!("delete project {}", project),
+        }
+    }
+}
+
+impl Event {
+    pub fn describe(&self) -> String {
+        self.change.describe(&self.project)
+    }
+
+    /// Replays this event onto `store`
+    pub fn apply(&self, store: &mut Store) {
+        apply(store, &self.project, &self.change, self.next_id);
+        store.set_seq(self.seq);
+    }
+
+    /// Reads one logged event, upgrading the todos it carries if it was
+    /// logged with an older schema
+    pub fn parse(data: &str) -> Result<Event, serde_json::Error> {
+        let mut value: Value = serde_json::from_str(data)?;
+        if !migrate::upgrade_change(&mut value).map_err(serde::de::Error::custom)? {
+            // Parsed from the text again so errors point at a position in it
+            return serde_json::from_str(data);
+        }
+
+        serde_json::from_value(value)
+    }
+}
+
+/// Makes `change` to `project`, leaving it with `next_id` if given
+pub fn apply(store: &mut Store, project: &str, change: &Change, next_id: Option<u64>) {
+    match change {
+        Change::CreateProject => {
+            let _ = store.create(project);
+        }
+        Change::DeleteProject => {
+            let _ = store.delete(project);
+        }
+        change => {
+            let Ok(todos) = store.project_mut(project) else {
+                return;
+            };
+            match change {
+                Change::Add { todo }
+                | Change::Status { todo, .. }
+                | Change::Rename { todo, .. }
+                | Change::Update { todo, .. } => todos.put(todo.clone()),
+                Change::Remove { id, .. } => todos.items.retain(|x| x.id != *id),
+                Change::Trash { entry } => {
+                    todos.items.retain(|x| x.id != entry.todo.id);
+                    todos.trash.retain(|x| x.todo.id != entry.todo.id);
+                    todos.trash.push(entry.clone());
+                    todos.trash.sort_by_key(|x| x.todo.id);
+                }
+                Change::Restore { todo } => {
+                    todos.trash.retain(|x| x.todo.id != todo.id);
+                    todos.put(todo.clone());
+                }
+                Change::Purge { id, .. } => todos.trash.retain(|x| x.todo.id != *id),
+                Change::Reset => *todos = TodoList::new(),
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(next_id) = next_id
+        && let Ok(todos) = store.project_mut(project)
+    {
+        todos.set_next_id(next_id);
+    }
+}
+
+/// The events that turn `before` into `after`, numbered on from `before`
+pub fn between(before: &Store, after: &Store) -> Vec<Event> {
+    let (at, actor) = (Utc::now(), actor());
+
+    diff(before, after)
+        .into_iter()
+        .zip(before.seq() + 1..)
+        .map(|((project, change), seq)| Event {
+            seq,
+            at,
+            actor: actor.clone(),
+            next_id: after.project(&project).ok().map(TodoList::next_id),
+            project,
+            schema_version: migrate::SCHEMA_VERSION,
+            change,
+        })
+        .collect()
+}
+
+/// Changes that turn `before` into `after`, per project
+pub fn diff(before: &Store, after: &Store) -> Vec<(String, Change)> {
+    let mut changes = Vec::new();
+    let empty = TodoList::new();
+
+    for (name, _) in before.projects() {
+        if after.project(name).is_err() {
+            changes.push((name.clone(), Change::DeleteProject));
+        }
+    }
+
+    for (name, list) in after.projects() {
+        let mut old = match before.project(name) {
+            Ok(old) => old,
+            Err(_) => {
+                changes.push((name.clone(), Change::CreateProject));
+                &empty
+            }
+        };
+        // Ids only go down when the list was replaced by an empty one
+        if list.next_id() < old.next_id() {
+            changes.push((name.clone(), Change::Reset));
+            old = &empty;
+        }
+
+        for todo in &old.items {
+            if list.get(todo.id).is_some() {
+                continue;
+            }
+            let change = match list.trashed(todo.id) {
+                Some(entry) if old.trashed(todo.id).is_none() => Change::Trash {
+                    entry: entry.clone(),
+                },
+                _ => Change::Remove {
+                    id: todo.id,
+                    title: todo.title.clone(),
+                },
+            };
+            changes.push((name.clone(), change));
+        }
+        for todo in &list.items {
+            let todo = todo.clone();
+            let change = match old.get(todo.id) {
+                None if old.trashed(todo.id).is_some() && list.trashed(todo.id).is_none() => {
+                    Change::Restore { todo }
+                }
+                None => Change::Add { todo },
+                Some(x) if *x != todo => edit(x, todo),
+                Some(_) => continue,
+            };
+            changes.push((name.clone(), change));
+        }
+
+        for entry in &old.trash {
+            let id = entry.todo.id;
+            if list.trashed(id).is_none() && list.get(id).is_none() {
+                let title = entry.todo.title.clone();
+                changes.push((name.clone(), Change::Purge { id, title }));
+            }
+        }
+        for entry in &list.trash {
+            let id = entry.todo.id;
+            if old.trashed(id).is_none() && old.get(id).is_none() {
+                let entry = entry.clone();
+                changes.push((name.clone(), Change::Trash { entry }));
+            }
+        }
+    }
+
+    changes
+}
+
+// Names the edit after what changed, leaving timestamps out as they change
+// along with everything else
+fn edit(old: &Todo, todo: Todo) -> Change {
+    let mut fields = Vec::new();
+    let mut changed = |name: &str, x: bool| {
+        if x {
+            fields.push(name.to_string());
+        }
+    };
+    changed("title", old.title != todo.title);
+    changed("status", old.status != todo.status);
+    changed("due", old.due != todo.due);
+    changed("priority", old.priority != todo.priority);
+    changed("tags", old.tags != todo.tags);
+    changed("notes", old.notes != todo.notes);
+    changed("parent", old.parent != todo.parent);
+    changed("blocked-by", old.blocked_by != todo.blocked_by);
+    changed("recurrence", old.recurrence != todo.recurrence);
+
+    match fields.as_slice() {
+        [x] if x == "status" => Change::Status {
+            from: old.status,
+            todo,
+        },
+        [x] if x == "title" => Change::Rename {
+            from: old.title.clone(),
+            todo,
+        },
+        _ => Change::Update { todo, fields },
+    }
+}
+
+/// `$USER via <program>`, recorded with every event
+pub fn actor() -> String {
+    let user = env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| String::from("unknown"));
+    let program = env::args_os()
+        .next()
+        .and_then(|x| {
+            Path::new(&x)
+                .file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| String::from("todo"));
+
+    format!("{} via {}", user, program)
+}
@@ -1,21 +1,500 @@
-use super::todo::TodoList;
-use std::fs;
-use std::path::Path;
+use super::events::Event;
+use super::history::{Entry, History, Snapshots};
+use super::migrate;
+use super::store::Store;
+use super::todo::{Todo, TodoList};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
-const TODO_FILE: &str = "resource/todo.json";
+#[derive(Serialize)]
+struct StoredFile<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    store: &'a Store,
+}
+
+#[derive(Serialize)]
+struct StoredHistory<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    history: &'a History,
+}
+
+#[derive(Debug)]
+pub enum FileError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Corrupt {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl FileError {
+    fn io(path: &Path, source: io::Error) -> Self {
+        FileError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    fn corrupt(path: &Path, error: serde_json::Error) -> Self {
+        // serde_json appends the position to its message, it is reported separately
+        let message = error.to_string();
+        let message = match message.rfind(" at line ") {
+            Some(index) => message[..index].to_string(),
+            None => message,
+        };
+
+        FileError::Corrupt {
+            path: path.to_path_buf(),
+            line: error.line(),
+            column: error.column(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            FileError::Corrupt {
+                path,
+                line,
+                column,
+                message,
+            } if *line == 0 => write!(f, "{} is corrupt: {}", path.display(), message),
+            FileError::Corrupt {
+                path,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "{} is corrupt at line {}, column {}: {}",
+                path.display(),
+                line,
+                column,
+                message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
 
-pub fn load_todos() -> TodoList {
-    if !Path::new(TODO_FILE).exists() {
-        return TodoList::new();
+/// Reads the store, or an empty one if the file does not exist yet. A file
+/// that cannot be parsed is an error rather than an empty list, so that the
+/// next save cannot overwrite it. Files written with an older schema are
+/// upgraded in memory after copying them to a versioned backup.
+pub fn load_todos(path: &Path) -> Result<Store, FileError> {
+    if !path.exists() {
+        return Ok(Store::new());
     }
 
-    let data = fs::read_to_string(TODO_FILE).expect("Failed to read todo.json");
+    let data = fs::read_to_string(path).map_err(|e| FileError::io(path, e))?;
+    let mut value: Value = serde_json::from_str(&data).map_err(|e| FileError::corrupt(path, e))?;
 
-    serde_json::from_str(&data).unwrap_or_else(|_| TodoList::new())
+    let version = migrate::version_of(&value);
+    if version == migrate::SCHEMA_VERSION {
+        // Parsed from the text again so errors point at a position in the file
+        return serde_json::from_str(&data).map_err(|e| FileError::corrupt(path, e));
+    }
+
+    let applied = migrate::upgrade(&mut value).map_err(|e| FileError::Corrupt {
+        path: path.to_path_buf(),
+        line: 0,
+        column: 0,
+        message: e,
+    })?;
+    if !applied.is_empty() {
+        let backup = upgrade_backup_path(path, version);
+        if !backup.exists() {
+            fs::write(&backup, &data).map_err(|e| FileError::io(&backup, e))?;
+        }
+    }
+
+    serde_json::from_value(value).map_err(|e| FileError::corrupt(path, e))
 }
 
-pub fn save_todos(todo: &TodoList) {
-    let data = serde_json::to_string_pretty(todo).expect("Failed to serialize todos");
+/// Rebuilds a store from a file that is valid JSON but holds todos that no
+/// longer deserialize, returning it with the number of todos dropped
+pub fn salvage(path: &Path) -> Option<(Store, usize)> {
+    let data = fs::read_to_string(path).ok()?;
+    let mut value: Value = serde_json::from_str(&data).ok()?;
+    let mut dropped = 0;
+
+    let lists: Vec<&mut Value> = match value.get_mut("projects") {
+        Some(Value::Object(projects)) => projects.values_mut().collect(),
+        Some(_) => return None,
+        None => vec![&mut value],
+    };
+    for list in lists {
+        let items = list.get_mut("items")?.as_array_mut()?;
+        let before = items.len();
+        items.retain(|x| serde_json::from_value::<Todo>(x.clone()).is_ok());
+        dropped += before - items.len();
+    }
+
+    let store = if value.get("projects").is_some() {
+        serde_json::from_value(value).ok()?
+    } else {
+        Store::from(serde_json::from_value::<TodoList>(value).ok()?)
+    };
+
+    Some((store, dropped))
+}
+
+/// Moves a damaged file aside and writes `store` in its place. The existing
+/// backup is left alone so it is not replaced by the damaged copy.
+pub fn replace_corrupt(path: &Path, store: &Store) -> Result<PathBuf, FileError> {
+    let corrupt = sibling(path, "corrupt");
+    fs::rename(path, &corrupt).map_err(|e| FileError::io(path, e))?;
+    save_todos(path, store)?;
+
+    Ok(corrupt)
+}
+
+/// Writes the store next to `path` first and renames it into place, so a
+/// crash leaves either the old or the new file, never a truncated one. The
+/// previous version is kept as a `.bak` file. Through `Storage` the todo
+/// file is a snapshot written every `SNAPSHOT_INTERVAL` events, so the backup
+/// can be that many changes behind; `doctor` replays the log onto it.
+pub fn save_todos(path: &Path, todo: &Store) -> Result<(), FileError> {
+    let stored = StoredFile {
+        schema_version: migrate::SCHEMA_VERSION,
+        store: todo,
+    };
+    let data = serde_json::to_string_pretty(&stored).map_err(|e| FileError::io(path, e.into()))?;
+
+    write_atomic(path, &data, true)
+}
+
+/// Undo history kept next to the store, empty if there is none yet. One
+/// saved before entries held changes is converted, given the `current` store
+/// and archive.
+pub fn load_history<E: From<FileError>>(
+    path: &Path,
+    current: impl FnOnce() -> Result<(Store, Store), E>,
+) -> Result<History, E> {
+    let history = history_path(path);
+    if !history.exists() {
+        return Ok(History::default());
+    }
+
+    let data = fs::read_to_string(&history).map_err(|e| FileError::io(&history, e))?;
+    let mut value: Value =
+        serde_json::from_str(&data).map_err(|e| FileError::corrupt(&history, e))?;
+    let invalid = |message: String| FileError::Corrupt {
+        path: history.clone(),
+        line: 0,
+        column: 0,
+        message,
+    };
+
+    let Some(version) = value.get("schema_version").and_then(Value::as_u64) else {
+        let snapshots = Snapshots::parse(value).map_err(invalid)?;
+        let (store, archive) = current()?;
+        return Ok(snapshots.into_history(&store, &archive));
+    };
+    if version as u32 == migrate::SCHEMA_VERSION {
+        // Parsed from the text again so errors point at a position in the file
+        return Ok(serde_json::from_str(&data).map_err(|e| FileError::corrupt(&history, e))?);
+    }
+
+    let mut entries = |stack: &str| -> Result<Vec<Entry>, FileError> {
+        let entries = value.get_mut(stack).and_then(Value::as_array_mut);
+        entries
+            .into_iter()
+            .flatten()
+            .map(|x| Entry::parse(x.take(), version as u32).map_err(invalid))
+            .collect()
+    };
+    let undo = entries("undo")?;
+    let redo = entries("redo")?;
+
+    Ok(History::from_entries(undo, redo))
+}
+
+pub fn save_history(path: &Path, history: &History) -> Result<(), FileError> {
+    let history_path = history_path(path);
+    let stored = StoredHistory {
+        schema_version: migrate::SCHEMA_VERSION,
+        history,
+    };
+    let data =
+        serde_json::to_string(&stored).map_err(|e| FileError::io(&history_path, e.into()))?;
+
+    write_atomic(&history_path, &data, false)
+}
+
+/// Removes the history kept next to the store, once a backend has taken it
+pub fn remove_history(path: &Path) -> Result<(), FileError> {
+    let history = history_path(path);
+    fs::remove_file(&history).map_err(|e| FileError::io(&history, e))
+}
+
+pub fn history_exists(path: &Path) -> bool {
+    history_path(path).exists()
+}
+
+fn history_path(path: &Path) -> PathBuf {
+    sibling(path, "history.json")
+}
+
+fn write_atomic(path: &Path, data: &str, keep_backup: bool) -> Result<(), FileError> {
+    let dir = path.parent().filter(|x| !x.as_os_str().is_empty());
+    if let Some(dir) = dir {
+        fs::create_dir_all(dir).map_err(|e| FileError::io(dir, e))?;
+    }
+
+    let temp = sibling(path, "tmp");
+    write_synced(&temp, data.as_bytes()).map_err(|e| FileError::io(&temp, e))?;
+
+    if keep_backup && path.exists() {
+        let backup = backup_path(path);
+        fs::copy(path, &backup).map_err(|e| FileError::io(&backup, e))?;
+    }
+
+    fs::rename(&temp, path).map_err(|e| FileError::io(path, e))?;
+
+    // Persist the rename itself; not every platform can open a directory
+    if let Ok(dir) = File::open(dir.unwrap_or(Path::new("."))) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+/// Advisory lock shared by every process using the same todo file, held
+/// from load to save so concurrent writers cannot lose each other's changes.
+/// It is taken on a separate `.lock` file because saving replaces the todo
+/// file itself. Released when dropped.
+pub struct FileLock {
+    _file: File,
+}
+
+pub fn lock(path: &Path) -> Result<FileLock, FileError> {
+    let lock_path = sibling(path, "lock");
+    if let Some(dir) = path.parent().filter(|x| !x.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| FileError::io(dir, e))?;
+    }
+
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| FileError::io(&lock_path, e))?;
+    file.lock().map_err(|e| FileError::io(&lock_path, e))?;
+
+    Ok(FileLock { _file: file })
+}
+
+/// Modification times and sizes of the todo file and its event log, used
+/// to notice changes made by other processes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    store: Option<(SystemTime, u64)>,
+    events: Option<(SystemTime, u64)>,
+}
+
+/// `None` when neither file exists
+pub fn stamp(path: &Path) -> Option<Stamp> {
+    let of = |path: &Path| {
+        let metadata = fs::metadata(path).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
+    };
+
+    match (of(path), of(&events_path(path))) {
+        (None, None) => None,
+        (store, events) => Some(Stamp { store, events }),
+    }
+}
+
+/// Events logged after `since`, oldest first, including those rotated out
+/// of the log by `rotate_events`
+pub fn load_events(path: &Path, since: u64) -> Result<Vec<Event>, FileError> {
+    let recent = load_recent_events(path, since)?;
+    // Nothing older is needed when the recent log goes back far enough
+    if recent.first().is_some_and(|x| x.seq <= since + 1) {
+        return Ok(recent);
+    }
+
+    let mut events = read_log(&rotated_events_path(path), since)?;
+    // A rotation cut short by a crash can leave an event in both logs
+    for event in recent {
+        if events.last().is_none_or(|x| x.seq < event.seq) {
+            events.push(event);
+        }
+    }
+
+    Ok(events)
+}
+
+/// Events logged after `since` that were not rotated yet, which are all of
+/// those after the last snapshot
+pub fn load_recent_events(path: &Path, since: u64) -> Result<Vec<Event>, FileError> {
+    read_log(&events_path(path), since)
+}
+
+fn read_log(log: &Path, since: u64) -> Result<Vec<Event>, FileError> {
+    if !log.exists() {
+        return Ok(Vec::new());
+    }
+
+    let data = fs::read_to_string(log).map_err(|e| FileError::io(log, e))?;
+    let mut events: Vec<Event> = Vec::new();
+    for (index, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Event::parse(line) {
+            Ok(event) if event.seq > since && events.last().is_none_or(|x| x.seq < event.seq) => {
+                events.push(event)
+            }
+            Ok(_) => {}
+            // A crash while appending can only tear the very last line
+            Err(_) if index + 1 == data.lines().count() && !data.ends_with('\n') => break,
+            Err(e) => {
+                return Err(FileError::Corrupt {
+                    path: log.to_path_buf(),
+                    line: index + 1,
+                    column: e.column(),
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+/// Moves the events logged so far to the end of the rotated log, once a
+/// snapshot covers them, so that loading only reads the events after it
+pub fn rotate_events(path: &Path) -> Result<(), FileError> {
+    let log = events_path(path);
+    if !log.exists() {
+        return Ok(());
+    }
+
+    let mut data = fs::read(&log).map_err(|e| FileError::io(&log, e))?;
+    // A torn last line is dropped rather than glued to the next event
+    let end = data.iter().rposition(|x| *x == b'\n').map_or(0, |x| x + 1);
+    data.truncate(end);
+    let rotated = rotated_events_path(path);
+    let mut file = File::options()
+        .create(true)
+        .append(true)
+        .open(&rotated)
+        .map_err(|e| FileError::io(&rotated, e))?;
+    file.write_all(&data)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| FileError::io(&rotated, e))?;
+
+    fs::remove_file(&log).map_err(|e| FileError::io(&log, e))
+}
+
+/// What could be read from a damaged event log, by 1-based line number
+pub struct SalvagedEvents {
+    pub events: Vec<(usize, Event)>,
+    pub unreadable: Vec<usize>,
+}
+
+pub fn salvage_events(path: &Path) -> Result<SalvagedEvents, FileError> {
+    let log = events_path(path);
+    let data = fs::read_to_string(&log).map_err(|e| FileError::io(&log, e))?;
+
+    let mut events = Vec::new();
+    let mut unreadable = Vec::new();
+    for (index, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Event::parse(line) {
+            Ok(event) => events.push((index + 1, event)),
+            Err(_) => unreadable.push(index + 1),
+        }
+    }
+
+    Ok(SalvagedEvents { events, unreadable })
+}
+
+/// Moves a damaged event log aside and writes `events` in its place
+pub fn replace_corrupt_events(path: &Path, events: &[Event]) -> Result<PathBuf, FileError> {
+    let log = events_path(path);
+    let corrupt = sibling(&log, "corrupt");
+    fs::rename(&log, &corrupt).map_err(|e| FileError::io(&log, e))?;
+    append_events(path, events)?;
+
+    Ok(corrupt)
+}
+
+pub fn append_events(path: &Path, events: &[Event]) -> Result<(), FileError> {
+    let log = events_path(path);
+    if let Some(dir) = log.parent().filter(|x| !x.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| FileError::io(dir, e))?;
+    }
+
+    let mut data = String::new();
+    for event in events {
+        let line = serde_json::to_string(event).map_err(|e| FileError::io(&log, e.into()))?;
+        data.push_str(&line);
+        data.push('\n');
+    }
+
+    let mut file = File::options()
+        .create(true)
+        .append(true)
+        .open(&log)
+        .map_err(|e| FileError::io(&log, e))?;
+    file.write_all(data.as_bytes())
+        .and_then(|_| file.sync_all())
+        .map_err(|e| FileError::io(&log, e))
+}
+
+pub fn events_path(path: &Path) -> PathBuf {
+    sibling(path, "events.jsonl")
+}
+
+fn rotated_events_path(path: &Path) -> PathBuf {
+    sibling(path, "events.old.jsonl")
+}
+
+/// Copy of a file kept before upgrading it from schema `version`
+pub fn upgrade_backup_path(path: &Path, version: u32) -> PathBuf {
+    sibling(path, &format!("v{}.bak", version))
+}
+
+/// Store of archived todos kept next to the active one
+pub fn archive_path(path: &Path) -> PathBuf {
+    sibling(path, "archive.json")
+}
+
+/// Copy of the previous version kept by `save_todos`
+pub fn backup_path(path: &Path) -> PathBuf {
+    sibling(path, "bak")
+}
+
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
+}
 
-    fs::write(TODO_FILE, data).expect("Failed to write todos to file")
+fn write_synced(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()
 }
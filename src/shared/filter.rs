@@ -0,0 +1,461 @@
+//! Filter expressions such as `status:open tag:backend due<friday -tag:someday`.
+//!
+//! Terms next to each other must all match, `or` matches either side, a
+//! leading `-` negates a term and parentheses group. A term is either
+//! `field:value`, a comparison like `due<=2030-01-31` or `priority>low`, or
+//! a bare word that is looked for in the title and notes. Values with spaces
+//! go in double quotes.
+
+use super::todo::{Priority, Status, Todo, normalize_tag};
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, Utc, Weekday};
+use clap::ValueEnum;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// A syntax error, at a 1-based character column of the expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    All(Vec<Expr>),
+    Any(Vec<Expr>),
+    Not(Box<Expr>),
+    Cond(Cond),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Is,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Cond {
+    /// A bare word, in the title or the notes
+    Text(String),
+    Title(String),
+    Notes(String),
+    Tag(String),
+    Status(Status),
+    Is(State),
+    Id(Op, u64),
+    Priority(Op, Priority),
+    /// `None` is `due:none`
+    Due(Op, Option<NaiveDate>),
+    Created(Op, NaiveDate),
+    Updated(Op, NaiveDate),
+    Completed(Op, NaiveDate),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Open,
+    Done,
+    Overdue,
+    Recurring,
+    Subtask,
+}
+
+impl Filter {
+    /// Parses `input`, resolving relative dates such as `today` or
+    /// `friday` against `today`
+    pub fn parse(input: &str, today: NaiveDate) -> Result<Filter, FilterError> {
+        let tokens = lex(input)?;
+        let mut parser = Parser {
+            tokens,
+            index: 0,
+            today,
+            end: input.chars().count() + 1,
+            depth: 0,
+        };
+
+        let expr = parser.any()?;
+        if let Some(token) = parser.peek() {
+            return Err(error(token.column, "Unexpected ')'"));
+        }
+
+        Ok(Filter { expr })
+    }
+
+    pub fn matches(&self, todo: &Todo, today: NaiveDate) -> bool {
+        self.expr.matches(todo, today)
+    }
+}
+
+impl Expr {
+    fn matches(&self, todo: &Todo, today: NaiveDate) -> bool {
+        match self {
+            Expr::All(items) => items.iter().all(|x| x.matches(todo, today)),
+            Expr::Any(items) => items.iter().any(|x| x.matches(todo, today)),
+            Expr::Not(expr) => !expr.matches(todo, today),
+            Expr::Cond(cond) => cond.matches(todo, today),
+        }
+    }
+}
+
+impl Cond {
+    fn matches(&self, todo: &Todo, today: NaiveDate) -> bool {
+        match self {
+            Cond::Text(text) => contains(&todo.title, text) || contains(&todo.notes, text),
+            Cond::Title(text) => contains(&todo.title, text),
+            Cond::Notes(text) => contains(&todo.notes, text),
+            Cond::Tag(tag) => todo.has_tag(tag),
+            Cond::Status(status) => todo.status == *status,
+            Cond::Is(State::Open) => !todo.is_done(),
+            Cond::Is(State::Done) => todo.is_done(),
+            Cond::Is(State::Overdue) => !todo.is_done() && todo.due.is_some_and(|x| x < today),
+            Cond::Is(State::Recurring) => todo.recurrence.is_some(),
+            Cond::Is(State::Subtask) => todo.parent.is_some(),
+            Cond::Id(op, id) => compare(*op, todo.id.cmp(id)),
+            // Higher priorities are greater, so `priority>low` means medium or high
+            Cond::Priority(op, priority) => compare(*op, priority.cmp(&todo.priority)),
+            Cond::Due(Op::Is, date) => todo.due == *date,
+            Cond::Due(op, date) => todo
+                .due
+                .zip(*date)
+                .is_some_and(|(due, date)| compare(*op, due.cmp(&date))),
+            Cond::Created(op, date) => on(*op, todo.created_at, *date),
+            Cond::Updated(op, date) => on(*op, todo.updated_at, *date),
+            Cond::Completed(op, date) => on(*op, todo.completed_at, *date),
+        }
+    }
+}
+
+fn contains(text: &str, word: &str) -> bool {
+    text.to_lowercase().contains(&word.to_lowercase())
+}
+
+fn compare(op: Op, ordering: Ordering) -> bool {
+    match op {
+        Op::Is => ordering == Ordering::Equal,
+        Op::Lt => ordering == Ordering::Less,
+        Op::Le => ordering != Ordering::Greater,
+        Op::Gt => ordering == Ordering::Greater,
+        Op::Ge => ordering != Ordering::Less,
+    }
+}
+
+// Timestamps are compared by their local date
+fn on(op: Op, time: Option<DateTime<Utc>>, date: NaiveDate) -> bool {
+    time.is_some_and(|x| compare(op, x.with_timezone(&Local).date_naive().cmp(&date)))
+}
+
+fn error(column: usize, message: &str) -> FilterError {
+    FilterError {
+        column,
+        message: message.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Open,
+    Close,
+    Not,
+    Or,
+    And,
+    Word(String),
+    Field {
+        name: String,
+        op: Op,
+        value: String,
+        // Where the value starts, for errors about it
+        at: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: Kind,
+    column: usize,
+}
+
+fn lex(input: &str) -> Result<Vec<Token>, FilterError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let column = index + 1;
+        match chars[index] {
+            c if c.is_whitespace() => index += 1,
+            '(' => {
+                tokens.push(Token {
+                    kind: Kind::Open,
+                    column,
+                });
+                index += 1;
+            }
+            ')' => {
+                tokens.push(Token {
+                    kind: Kind::Close,
+                    column,
+                });
+                index += 1;
+            }
+            '-' => {
+                tokens.push(Token {
+                    kind: Kind::Not,
+                    column,
+                });
+                index += 1;
+            }
+            _ => {
+                let name_end = (index..chars.len())
+                    .find(|x| !(chars[*x].is_alphanumeric() || chars[*x] == '_'))
+                    .unwrap_or(chars.len());
+                let op = match chars.get(name_end..(name_end + 2).min(chars.len())) {
+                    Some(['<', '=']) => Some((Op::Le, 2)),
+                    Some(['>', '=']) => Some((Op::Ge, 2)),
+                    Some([':' | '=', ..]) => Some((Op::Is, 1)),
+                    Some(['<', ..]) => Some((Op::Lt, 1)),
+                    Some(['>', ..]) => Some((Op::Gt, 1)),
+                    _ => None,
+                };
+
+                let kind = match op {
+                    Some((op, len)) if name_end > index => {
+                        let name: String = chars[index..name_end].iter().collect();
+                        let at = name_end + len;
+                        let (value, end) = word(&chars, at)?;
+                        if value.is_empty() {
+                            return Err(error(at + 1, &format!("Missing value for '{}'", name)));
+                        }
+                        index = end;
+                        Kind::Field {
+                            name: name.to_lowercase(),
+                            op,
+                            value,
+                            at: at + 1,
+                        }
+                    }
+                    _ => {
+                        let quoted = chars[index] == '"';
+                        let (value, end) = word(&chars, index)?;
+                        index = end;
+                        match value.to_lowercase().as_str() {
+                            "or" if !quoted => Kind::Or,
+                            "and" if !quoted => Kind::And,
+                            _ => Kind::Word(value),
+                        }
+                    }
+                };
+                tokens.push(Token { kind, column });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+// A quoted string or a run of characters up to a space or parenthesis,
+// returned with the index just past it
+fn word(chars: &[char], start: usize) -> Result<(String, usize), FilterError> {
+    if chars.get(start) == Some(&'"') {
+        let end = (start + 1..chars.len())
+            .find(|x| chars[*x] == '"')
+            .ok_or_else(|| error(start + 1, "Unclosed quote"))?;
+        return Ok((chars[start + 1..end].iter().collect(), end + 1));
+    }
+
+    let end = (start..chars.len())
+        .find(|x| chars[*x].is_whitespace() || chars[*x] == '(' || chars[*x] == ')')
+        .unwrap_or(chars.len());
+    Ok((chars[start..end].iter().collect(), end))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    today: NaiveDate,
+    // Column reported for errors at the end of the input
+    end: usize,
+    // How many `-` and `(` enclose the current term
+    depth: usize,
+}
+
+/// Deepest nesting of `-` and `(` a filter may use, so that a hostile
+/// expression cannot overflow the stack of the recursive parser
+pub const MAX_DEPTH: usize = 64;
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+
+    // any := all ("or" all)*
+    fn any(&mut self) -> Result<Expr, FilterError> {
+        let mut items = vec![self.all()?];
+        while self.peek().is_some_and(|x| x.kind == Kind::Or) {
+            self.next();
+            items.push(self.all()?);
+        }
+
+        Ok(if items.len() == 1 {
+            items.remove(0)
+        } else {
+            Expr::Any(items)
+        })
+    }
+
+    // all := unary ("and"? unary)*
+    fn all(&mut self) -> Result<Expr, FilterError> {
+        let mut items = vec![self.unary()?];
+        loop {
+            match self.peek().map(|x| &x.kind) {
+                None | Some(Kind::Or) | Some(Kind::Close) => break,
+                Some(Kind::And) => {
+                    self.next();
+                    items.push(self.unary()?);
+                }
+                Some(_) => items.push(self.unary()?),
+            }
+        }
+
+        Ok(if items.len() == 1 {
+            items.remove(0)
+        } else {
+            Expr::All(items)
+        })
+    }
+
+    // unary := "-" unary | "(" any ")" | term
+    fn unary(&mut self) -> Result<Expr, FilterError> {
+        let Some(token) = self.next() else {
+            return Err(error(self.end, "Expected a filter"));
+        };
+
+        match token.kind {
+            Kind::Not | Kind::Open => self.nested(token),
+            Kind::Close => Err(error(token.column, "Unexpected ')'")),
+            Kind::Or | Kind::And => Err(error(token.column, "Expected a filter")),
+            Kind::Word(text) => Ok(Expr::Cond(Cond::Text(text))),
+            Kind::Field {
+                name,
+                op,
+                value,
+                at,
+            } => self
+                .field(&name, op, &value, token.column, at)
+                .map(Expr::Cond),
+        }
+    }
+
+    // The "-" unary and "(" any ")" cases, which nest
+    fn nested(&mut self, token: Token) -> Result<Expr, FilterError> {
+        if self.depth == MAX_DEPTH {
+            return Err(error(token.column, "The filter is nested too deeply"));
+        }
+        self.depth += 1;
+
+        let expr = if token.kind == Kind::Not {
+            Expr::Not(Box::new(self.unary()?))
+        } else {
+            let expr = self.any()?;
+            match self.next() {
+                Some(Token {
+                    kind: Kind::Close, ..
+                }) => expr,
+                Some(other) => return Err(error(other.column, "Expected ')'")),
+                None => return Err(error(self.end, "Expected ')'")),
+            }
+        };
+
+        self.depth -= 1;
+        Ok(expr)
+    }
+
+    fn field(
+        &self,
+        name: &str,
+        op: Op,
+        value: &str,
+        column: usize,
+        at: usize,
+    ) -> Result<Cond, FilterError> {
+        let only_is = |cond: Cond| {
+            if op == Op::Is {
+                Ok(cond)
+            } else {
+                Err(error(column, &format!("'{}' cannot be compared", name)))
+            }
+        };
+        let invalid = |what: &str| error(at, &format!("Invalid {} '{}'", what, value));
+
+        match name {
+            "title" => only_is(Cond::Title(value.to_string())),
+            "notes" => only_is(Cond::Notes(value.to_string())),
+            "tag" => only_is(Cond::Tag(normalize_tag(value).map_err(|_| invalid("tag"))?)),
+            "status" => only_is(Cond::Status(
+                Status::from_str(value, true).map_err(|_| invalid("status"))?,
+            )),
+            "is" => {
+                let state = match value.to_lowercase().as_str() {
+                    "open" => State::Open,
+                    "done" => State::Done,
+                    "overdue" => State::Overdue,
+                    "recurring" => State::Recurring,
+                    "subtask" => State::Subtask,
+                    _ => return Err(invalid("state")),
+                };
+                only_is(Cond::Is(state))
+            }
+            "id" => Ok(Cond::Id(op, value.parse().map_err(|_| invalid("id"))?)),
+            "priority" => Ok(Cond::Priority(
+                op,
+                Priority::from_str(value, true).map_err(|_| invalid("priority"))?,
+            )),
+            "due" if value.eq_ignore_ascii_case("none") => only_is(Cond::Due(op, None)),
+            "due" => Ok(Cond::Due(op, Some(self.date(value, at)?))),
+            "created" => Ok(Cond::Created(op, self.date(value, at)?)),
+            "updated" => Ok(Cond::Updated(op, self.date(value, at)?)),
+            "completed" => Ok(Cond::Completed(op, self.date(value, at)?)),
+            _ => Err(error(column, &format!("Unknown field '{}'", name))),
+        }
+    }
+
+    // YYYY-MM-DD, today, tomorrow, yesterday or the next given weekday,
+    // today included
+    fn date(&self, value: &str, at: usize) -> Result<NaiveDate, FilterError> {
+        let today = self.today;
+        match value.to_lowercase().as_str() {
+            "today" => return Ok(today),
+            "tomorrow" => return Ok(today + Days::new(1)),
+            "yesterday" => return Ok(today - Days::new(1)),
+            _ => {}
+        }
+        if let Ok(day) = Weekday::from_str(value) {
+            let ahead =
+                (7 + day.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+            return Ok(today + Days::new(ahead as u64));
+        }
+
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| error(at, &format!("Invalid date '{}'", value)))
+    }
+}
@@ -0,0 +1,299 @@
+use super::events::{self, Change};
+use super::migrate;
+use super::store::Store;
+use super::todo::TodoList;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// How many operations can be undone
+pub const HISTORY_LIMIT: usize = 50;
+
+/// Recent operations, kept by the storage backend so that todo-cli and
+/// todo-api share them. Entries hold the changes that revert each operation
+/// and those that make it again rather than copies of the store, so undoing
+/// leaves alone whatever else changed since.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    // Oldest first
+    undo: Vec<Entry>,
+    // Next to redo last
+    redo: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Unique among the entries, so backends can store them one by one.
+    /// Ascending along `undo` and descending along `redo`.
+    pub id: u64,
+    pub operation: String,
+    pub at: DateTime<Utc>,
+    store: Steps,
+    // For entries that archived todos
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    archive: Option<Steps>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Steps {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+}
+
+/// A change to one project, like an event without its place in the log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Step {
+    project: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    next_id: Option<u64>,
+    #[serde(flatten)]
+    change: Change,
+}
+
+impl History {
+    pub fn from_entries(undo: Vec<Entry>, redo: Vec<Entry>) -> Self {
+        History { undo, redo }
+    }
+
+    pub fn undo_entries(&self) -> &[Entry] {
+        &self.undo
+    }
+
+    pub fn redo_entries(&self) -> &[Entry] {
+        &self.redo
+    }
+
+    /// Remembers `operation`, which turned `before` into `after`
+    pub fn record(&mut self, operation: String, before: &Store, after: &Store) {
+        let id = self.next_id();
+        self.push(Entry {
+            id,
+            operation,
+            at: Utc::now(),
+            store: Steps::between(before, after),
+            archive: None,
+        });
+    }
+
+    /// Like `record` for archiving, which changes the archive as well
+    pub fn record_archive(
+        &mut self,
+        operation: String,
+        (before, after): (&Store, &Store),
+        (archive_before, archive_after): (&Store, &Store),
+    ) {
+        let id = self.next_id();
+        self.push(Entry {
+            id,
+            operation,
+            at: Utc::now(),
+            store: Steps::between(before, after),
+            archive: Some(Steps::between(archive_before, archive_after)),
+        });
+    }
+
+    fn next_id(&self) -> u64 {
+        self.undo.last().map_or(1, |x| x.id + 1)
+    }
+
+    fn push(&mut self, entry: Entry) {
+        self.undo.push(entry);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Reverts the last operation, returning its name. `archive` only
+    /// changes when that operation archived todos.
+    pub fn undo(&mut self, store: &mut Store, archive: &mut Store) -> Option<String> {
+        let mut entry = self.undo.pop()?;
+        apply(&entry.store.undo, store);
+        if let Some(steps) = &entry.archive {
+            apply(&steps.undo, archive);
+        }
+        let operation = entry.operation.clone();
+        entry.at = Utc::now();
+        self.redo.push(entry);
+
+        Some(operation)
+    }
+
+    /// Makes the last undone operation again, returning its name
+    pub fn redo(&mut self, store: &mut Store, archive: &mut Store) -> Option<String> {
+        let mut entry = self.redo.pop()?;
+        apply(&entry.store.redo, store);
+        if let Some(steps) = &entry.archive {
+            apply(&steps.redo, archive);
+        }
+        let operation = entry.operation.clone();
+        entry.at = Utc::now();
+        self.undo.push(entry);
+
+        Some(operation)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+impl Entry {
+    /// Reads an entry saved with schema `version`, upgrading the todos its
+    /// changes carry like those of logged events
+    pub fn parse(mut value: Value, version: u32) -> Result<Entry, String> {
+        if version != migrate::SCHEMA_VERSION {
+            for side in ["store", "archive"] {
+                for stack in ["undo", "redo"] {
+                    let steps = value
+                        .get_mut(side)
+                        .and_then(|x| x.get_mut(stack))
+                        .and_then(Value::as_array_mut);
+                    for step in steps.into_iter().flatten() {
+                        if let Value::Object(step) = step {
+                            step.insert(String::from("schema_version"), json!(version));
+                        }
+                        migrate::upgrade_change(step)?;
+                    }
+                }
+            }
+        }
+
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+}
+
+impl Steps {
+    fn between(before: &Store, after: &Store) -> Steps {
+        Steps {
+            undo: steps(after, before),
+            redo: steps(before, after),
+        }
+    }
+}
+
+fn steps(from: &Store, to: &Store) -> Vec<Step> {
+    events::diff(from, to)
+        .into_iter()
+        .map(|(project, change)| Step {
+            next_id: to.project(&project).ok().map(TodoList::next_id),
+            project,
+            change,
+        })
+        .collect()
+}
+
+fn apply(steps: &[Step], store: &mut Store) {
+    for step in steps {
+        events::apply(store, &step.project, &step.change, step.next_id);
+    }
+}
+
+/// A history saved before entries held changes, as copies of the store, and
+/// of the archive when archiving, from before each operation or, for those
+/// undone, from after it
+#[derive(Deserialize)]
+pub struct Snapshots {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    operation: String,
+    at: DateTime<Utc>,
+    store: Store,
+    #[serde(default)]
+    archive: Option<Store>,
+}
+
+impl Snapshots {
+    /// Reads the saved history, whose copies have no schema version of
+    /// their own and are upgraded like a store file without one
+    pub fn parse(mut value: Value) -> Result<Snapshots, String> {
+        for stack in ["undo", "redo"] {
+            let entries = value.get_mut(stack).and_then(Value::as_array_mut);
+            for entry in entries.into_iter().flatten() {
+                for side in ["store", "archive"] {
+                    if let Some(store) = entry.get_mut(side).filter(|x| !x.is_null()) {
+                        migrate::upgrade(store)?;
+                    }
+                }
+            }
+        }
+
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+
+    /// Works out the changes between the copies, given the current `store`
+    /// and `archive`, which the undo entries lead up to and the redo
+    /// entries start from
+    pub fn into_history(self, store: &Store, archive: &Store) -> History {
+        let undone = self.undo.len();
+        let redo: Vec<Snapshot> = self.redo.into_iter().rev().collect();
+
+        // Every state in order, each operation leading from one to the next
+        let mut states: Vec<(&Store, Option<&Store>)> = Vec::new();
+        states.extend(self.undo.iter().map(|x| (&x.store, x.archive.as_ref())));
+        states.push((store, Some(archive)));
+        states.extend(redo.iter().map(|x| (&x.store, x.archive.as_ref())));
+
+        // Operations that left the archive alone have no copy of it
+        for index in (0..undone).rev() {
+            states[index].1 = states[index].1.or(states[index + 1].1);
+        }
+        for index in undone + 1..states.len() {
+            states[index].1 = states[index].1.or(states[index - 1].1);
+        }
+
+        let entry = |index: usize, snapshot: &Snapshot, from: usize, to: usize| {
+            let ((before, archive_before), (after, archive_after)) = (states[from], states[to]);
+            Entry {
+                id: index as u64 + 1,
+                operation: snapshot.operation.clone(),
+                at: snapshot.at,
+                store: Steps::between(before, after),
+                archive: snapshot.archive.as_ref().map(|_| {
+                    Steps::between(
+                        archive_before.unwrap_or(archive),
+                        archive_after.unwrap_or(archive),
+                    )
+                }),
+            }
+        };
+
+        let undo = self
+            .undo
+            .iter()
+            .enumerate()
+            .map(|(index, x)| entry(index, x, index, index + 1))
+            .collect();
+        let redo = redo
+            .iter()
+            .enumerate()
+            .map(|(index, x)| entry(undone + index, x, undone + index, undone + index + 1))
+            .rev()
+            .collect();
+
+        History { undo, redo }
+    }
+}
+
+/// Names what changed between two versions of the store, e.g.
+/// `delete [3] Buy milk` or `update [2] Walk dog, add [4] Feed cat`
+pub fn describe(before: &Store, after: &Store) -> String {
+    let changes: Vec<String> = events::diff(before, after)
+        .iter()
+        .map(|(project, change)| change.describe(project))
+        .collect();
+
+    match changes.len() {
+        0 => String::from("no change"),
+        1..=3 => changes.join(", "),
+        n => format!("{} and {} more", changes[..3].join(", "), n - 3),
+    }
+}
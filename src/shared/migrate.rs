@@ -0,0 +1,136 @@
+use serde_json::{Map, Value, json};
+
+/// Version written by this build, stored as `schema_version` in the file
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// One upgrade step from `from` to `from + 1`, applied to the raw JSON before
+/// it is deserialized. `apply` returns how many values it changed.
+pub struct Migration {
+    pub from: u32,
+    pub description: &'static str,
+    apply: fn(&mut Value) -> usize,
+}
+
+/// Every step in order; add one here whenever the stored format changes
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "move the single todo list into the default project",
+        apply: into_projects,
+    },
+    Migration {
+        from: 1,
+        description: "derive each todo's status from its done flag",
+        apply: status_from_done,
+    },
+];
+
+/// Files without `schema_version` predate it: version 1 introduced projects
+pub fn version_of(value: &Value) -> u32 {
+    match value.get("schema_version").and_then(Value::as_u64) {
+        Some(version) => version as u32,
+        None if value.get("projects").is_some() => 1,
+        None => 0,
+    }
+}
+
+pub fn pending(version: u32) -> Vec<&'static Migration> {
+    MIGRATIONS.iter().filter(|x| x.from >= version).collect()
+}
+
+/// Brings `value` up to `SCHEMA_VERSION`, returning the steps applied and
+/// the number of changes each made
+pub fn upgrade(value: &mut Value) -> Result<Vec<(&'static Migration, usize)>, String> {
+    let version = version_of(value);
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "Schema version {} is newer than the supported version {}",
+            version, SCHEMA_VERSION
+        ));
+    }
+
+    let applied = pending(version)
+        .into_iter()
+        .map(|x| (x, (x.apply)(value)))
+        .collect();
+
+    if let Value::Object(root) = value {
+        root.insert(String::from("schema_version"), json!(SCHEMA_VERSION));
+    }
+
+    Ok(applied)
+}
+
+/// Events and undo steps without `schema_version` were written with this one
+pub const FIRST_CHANGE_VERSION: u32 = 2;
+
+/// Brings a logged change, such as an event, up to `SCHEMA_VERSION` by
+/// running the steps above over the todo or trash entry it carries, returning
+/// whether it was written with an older schema
+pub fn upgrade_change(value: &mut Value) -> Result<bool, String> {
+    let version = value
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .map_or(FIRST_CHANGE_VERSION, |x| x as u32);
+    if version == SCHEMA_VERSION {
+        return Ok(false);
+    }
+    let Value::Object(change) = value else {
+        return Ok(false);
+    };
+
+    // Wrapped in a store of its own so the steps find it where they expect
+    let todo = change.remove("todo");
+    let entry = change.remove("entry");
+    let mut store = json!({
+        "schema_version": version,
+        "projects": { "default": {
+            "items": todo.iter().collect::<Vec<_>>(),
+            "trash": entry.iter().collect::<Vec<_>>(),
+        }},
+    });
+    upgrade(&mut store)?;
+
+    let list = &mut store["projects"]["default"];
+    if todo.is_some() {
+        change.insert(String::from("todo"), list["items"][0].take());
+    }
+    if entry.is_some() {
+        change.insert(String::from("entry"), list["trash"][0].take());
+    }
+    change.insert(String::from("schema_version"), json!(SCHEMA_VERSION));
+
+    Ok(true)
+}
+
+fn into_projects(value: &mut Value) -> usize {
+    let list = value.take();
+    let mut projects = Map::new();
+    projects.insert(String::from("default"), list);
+    *value = json!({ "projects": projects });
+
+    1
+}
+
+fn status_from_done(value: &mut Value) -> usize {
+    let Some(Value::Object(projects)) = value.get_mut("projects") else {
+        return 0;
+    };
+
+    let items = projects
+        .values_mut()
+        .filter_map(|x| x.get_mut("items").and_then(Value::as_array_mut))
+        .flatten()
+        .filter_map(Value::as_object_mut)
+        .filter(|x| !x.contains_key("status"));
+
+    let mut changed = 0;
+    for item in items {
+        let done = item.get("done").and_then(Value::as_bool).unwrap_or(false);
+        let status = if done { "done" } else { "open" };
+        item.insert(String::from("status"), json!(status));
+        changed += 1;
+    }
+
+    changed
+}
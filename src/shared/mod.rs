@@ -1,2 +1,15 @@
+pub mod config;
+pub mod edit;
+pub mod events;
 pub mod file;
+pub mod filter;
+pub mod history;
+pub mod migrate;
+pub mod output;
+pub mod recurrence;
+pub mod search;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod storage;
+pub mod store;
 pub mod todo;
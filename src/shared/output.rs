@@ -0,0 +1,196 @@
+use super::todo::{Priority, Status, Todo};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Output of read commands, for scripts and other tools
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// A JSON array of `TodoResponse`s
+    Json,
+    /// One `TodoResponse` per line
+    Jsonl,
+    Csv,
+    Tsv,
+    Table,
+    Markdown,
+}
+
+/// A todo as both binaries present it to other programs, in `todo-api`
+/// responses and `--format json`
+#[derive(Serialize)]
+pub struct TodoResponse {
+    pub id: u64,
+    pub title: String,
+    /// Kept for clients that predate `status`, true when done or cancelled
+    pub done: bool,
+    pub status: Status,
+    pub due: Option<NaiveDate>,
+    pub priority: Priority,
+    pub tags: Vec<String>,
+    pub notes: String,
+    pub parent_id: Option<u64>,
+    pub blocked_by: Vec<u64>,
+    pub recurrence: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl From<&Todo> for TodoResponse {
+    fn from(t: &Todo) -> Self {
+        Self {
+            id: t.id,
+            title: t.title.clone(),
+            done: t.is_done(),
+            status: t.status,
+            due: t.due,
+            priority: t.priority,
+            tags: t.tags.iter().cloned().collect(),
+            notes: t.notes.clone(),
+            parent_id: t.parent,
+            blocked_by: t.blocked_by.iter().copied().collect(),
+            recurrence: t.recurrence.as_ref().map(|x| x.to_string()),
+            created_at: t.created_at,
+            updated_at: t.updated_at,
+            completed_at: t.completed_at,
+        }
+    }
+}
+
+/// Fields of `TodoResponse` in the order csv and tsv columns are written
+const COLUMNS: [&str; 14] = [
+    "id",
+    "title",
+    "done",
+    "status",
+    "due",
+    "priority",
+    "tags",
+    "notes",
+    "parent_id",
+    "blocked_by",
+    "recurrence",
+    "created_at",
+    "updated_at",
+    "completed_at",
+];
+
+/// The short list of columns people read in `table` and `markdown`
+const SUMMARY: [&str; 6] = ["id", "status", "priority", "due", "title", "tags"];
+
+pub fn render(format: Format, todos: &[&Todo]) -> String {
+    let responses: Vec<TodoResponse> = todos.iter().map(|x| TodoResponse::from(*x)).collect();
+
+    match format {
+        Format::Json => {
+            let mut json = serde_json::to_string_pretty(&responses).unwrap_or_default();
+            json.push('\n');
+            json
+        }
+        Format::Jsonl => responses
+            .iter()
+            .map(|x| serde_json::to_string(x).unwrap_or_default() + "\n")
+            .collect(),
+        Format::Csv => delimited(&responses, ",", escape_csv),
+        Format::Tsv => delimited(&responses, "\t", escape_tsv),
+        Format::Table => table(&responses),
+        Format::Markdown => markdown(&responses),
+    }
+}
+
+// Fields as plain text, lists joined by spaces and missing values empty
+fn cells(response: &TodoResponse, columns: &[&str]) -> Vec<String> {
+    let value = serde_json::to_value(response).unwrap_or_default();
+
+    columns
+        .iter()
+        .map(|column| match &value[*column] {
+            Value::Null => String::new(),
+            Value::String(x) => x.clone(),
+            Value::Array(items) => items
+                .iter()
+                .map(|x| match x {
+                    Value::String(x) => x.clone(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            other => other.to_string(),
+        })
+        .collect()
+}
+
+fn delimited(responses: &[TodoResponse], separator: &str, escape: fn(&str) -> String) -> String {
+    let mut lines = vec![COLUMNS.join(separator)];
+    for response in responses {
+        let row: Vec<String> = cells(response, &COLUMNS)
+            .iter()
+            .map(|x| escape(x))
+            .collect();
+        lines.push(row.join(separator));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Tsv has no quoting, so tabs and line breaks are written as escapes
+fn escape_tsv(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn table(responses: &[TodoResponse]) -> String {
+    let rows: Vec<Vec<String>> = responses.iter().map(|x| cells(x, &SUMMARY)).collect();
+    let widths: Vec<usize> = SUMMARY
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            rows.iter()
+                .map(|x| x[i].chars().count())
+                .fold(header.len(), usize::max)
+        })
+        .collect();
+
+    let line = |cells: Vec<String>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        padded.join("  ").trim_end().to_string() + "\n"
+    };
+
+    let mut output = line(SUMMARY.iter().map(|x| x.to_uppercase()).collect());
+    for row in rows {
+        output.push_str(&line(row));
+    }
+    output
+}
+
+fn markdown(responses: &[TodoResponse]) -> String {
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+    let mut output = line(SUMMARY.iter().map(|x| x.to_string()).collect());
+    output.push_str(&line(SUMMARY.iter().map(|_| String::from("---")).collect()));
+    for response in responses {
+        let row = cells(response, &SUMMARY)
+            .iter()
+            .map(|x| x.replace('|', "\\|").replace('\n', " "))
+            .collect();
+        output.push_str(&line(row));
+    }
+    output
+}
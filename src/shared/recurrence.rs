@@ -0,0 +1,143 @@
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// How a todo repeats, written as `daily`, `weekly`, `monthly`, `yearly`,
+/// `every 3 days` or `every mon,wed,fri`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Recurrence {
+    Interval { every: u32, unit: Unit },
+    Weekdays(BTreeSet<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// Largest `every` accepted in a rule
+pub const MAX_EVERY: u32 = 1000;
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+impl Recurrence {
+    /// First occurrence strictly after `from`, or an error when it falls
+    /// past the last representable date
+    pub fn next(&self, from: NaiveDate) -> Result<NaiveDate, String> {
+        let next = match self {
+            Recurrence::Interval { every, unit } => match unit {
+                Unit::Day => from.checked_add_days(Days::new(*every as u64)),
+                Unit::Week => from.checked_add_days(Days::new(7 * *every as u64)),
+                Unit::Month => from.checked_add_months(Months::new(*every)),
+                Unit::Year => every
+                    .checked_mul(12)
+                    .and_then(|x| from.checked_add_months(Months::new(x))),
+            },
+            Recurrence::Weekdays(days) => (1..=7)
+                .map_while(|x| from.checked_add_days(Days::new(x)))
+                .find(|x| days.contains(&(x.weekday().num_days_from_monday() as u8))),
+        };
+
+        next.ok_or_else(|| {
+            format!(
+                "The next '{}' occurrence after {} is out of range",
+                self, from
+            )
+        })
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let rule = value.trim().to_lowercase();
+        let invalid = || format!("Invalid recurrence '{}'", value);
+
+        let interval = |every, unit| Ok(Recurrence::Interval { every, unit });
+        match rule.as_str() {
+            "daily" => return interval(1, Unit::Day),
+            "weekly" => return interval(1, Unit::Week),
+            "monthly" => return interval(1, Unit::Month),
+            "yearly" => return interval(1, Unit::Year),
+            _ => {}
+        }
+
+        let rest = rule.strip_prefix("every ").ok_or_else(invalid)?.trim();
+
+        if let Some((every, unit)) = rest.split_once(' ') {
+            let every: u32 = every.parse().map_err(|_| invalid())?;
+            if every == 0 || every > MAX_EVERY {
+                return Err(invalid());
+            }
+            let unit = match unit.trim().trim_end_matches('s') {
+                "day" => Unit::Day,
+                "week" => Unit::Week,
+                "month" => Unit::Month,
+                "year" => Unit::Year,
+                _ => return Err(invalid()),
+            };
+            return interval(every, unit);
+        }
+
+        let days = rest
+            .split(',')
+            .map(|x| {
+                Weekday::from_str(x.trim())
+                    .map(|x| x.num_days_from_monday() as u8)
+                    .map_err(|_| invalid())
+            })
+            .collect::<Result<BTreeSet<u8>, String>>()?;
+
+        Ok(Recurrence::Weekdays(days))
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(value: Recurrence) -> Self {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Recurrence::Interval { every: 1, unit } => {
+                let name = match unit {
+                    Unit::Day => "daily",
+                    Unit::Week => "weekly",
+                    Unit::Month => "monthly",
+                    Unit::Year => "yearly",
+                };
+                write!(f, "{}", name)
+            }
+            Recurrence::Interval { every, unit } => {
+                let name = match unit {
+                    Unit::Day => "days",
+                    Unit::Week => "weeks",
+                    Unit::Month => "months",
+                    Unit::Year => "years",
+                };
+                write!(f, "every {} {}", every, name)
+            }
+            Recurrence::Weekdays(days) => {
+                let names: Vec<&str> = days.iter().map(|x| WEEKDAYS[*x as usize]).collect();
+                write!(f, "every {}", names.join(","))
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use super::todo::{Todo, TodoList};
+use clap::ValueEnum;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// How a search query is matched, always ignoring case
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// The query anywhere in the text
+    #[default]
+    Substring,
+    /// Words starting with the query
+    Prefix,
+    Regex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Title,
+    Notes,
+    Tag,
+}
+
+/// Where a query matched one field of a todo
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldMatch {
+    pub field: Field,
+    pub text: String,
+    /// Start and end of every match, counted in characters
+    pub spans: Vec<(usize, usize)>,
+}
+
+pub struct Query {
+    text: String,
+    pattern: Regex,
+}
+
+impl Query {
+    pub fn new(query: &str, mode: SearchMode) -> Result<Query, String> {
+        if query.trim().is_empty() {
+            return Err(String::from("The search query is empty"));
+        }
+
+        let pattern = match mode {
+            SearchMode::Substring => regex::escape(query),
+            SearchMode::Prefix => format!(r"\b{}", regex::escape(query)),
+            SearchMode::Regex => query.to_string(),
+        };
+        let pattern = RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| format!("Invalid regex: {}", e))?;
+
+        Ok(Query {
+            text: query.to_string(),
+            pattern,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Matches in the title, notes and tags of `todo`, empty if there are none
+    pub fn matches(&self, todo: &Todo) -> Vec<FieldMatch> {
+        let fields = std::iter::once((Field::Title, todo.title.as_str()))
+            .chain(std::iter::once((Field::Notes, todo.notes.as_str())))
+            .chain(todo.tags.iter().map(|x| (Field::Tag, x.as_str())));
+
+        fields
+            .filter_map(|(field, text)| {
+                let spans: Vec<(usize, usize)> = self
+                    .pattern
+                    .find_iter(text)
+                    .filter(|x| !x.is_empty())
+                    .map(|x| (chars(text, x.start()), chars(text, x.end())))
+                    .collect();
+                (!spans.is_empty()).then(|| FieldMatch {
+                    field,
+                    text: text.to_string(),
+                    spans,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Todos matching `query`, those matching in the title first
+pub fn search<'a>(todos: &'a TodoList, query: &Query) -> Vec<(&'a Todo, Vec<FieldMatch>)> {
+    let mut hits: Vec<(&Todo, Vec<FieldMatch>)> = todos
+        .list()
+        .iter()
+        .map(|x| (x, query.matches(x)))
+        .filter(|(_, matches)| !matches.is_empty())
+        .collect();
+    hits.sort_by_key(|(todo, matches)| (matches[0].field != Field::Title, todo.id));
+
+    hits
+}
+
+/// Wraps every span of `text` in `start` and `end`, e.g. terminal colors
+pub fn highlight(text: &str, spans: &[(usize, usize)], start: &str, end: &str) -> String {
+    let mut result = String::new();
+    for (index, c) in text.chars().enumerate() {
+        if spans.iter().any(|(from, _)| *from == index) {
+            result.push_str(start);
+        }
+        result.push(c);
+        if spans.iter().any(|(_, to)| *to == index + 1) {
+            result.push_str(end);
+        }
+    }
+
+    result
+}
+
+fn chars(text: &str, byte: usize) -> usize {
+    text[..byte].chars().count()
+}
@@ -0,0 +1,450 @@
+use super::events::{self, Event};
+use super::file;
+use super::history::{Entry, History};
+use super::migrate;
+use super::storage::{Storage, StorageError};
+use super::store::Store;
+use super::todo::{Todo, TodoList, Trashed};
+use rusqlite::{Connection, OptionalExtension, Transaction, params};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Todos are kept as JSON documents so that new `Todo` fields need no schema
+// change; only the columns used to find and order rows are broken out
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS projects (
+        name TEXT PRIMARY KEY,
+        next_id INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS todos (
+        project TEXT NOT NULL REFERENCES projects(name) ON DELETE CASCADE,
+        id INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (project, id)
+    );
+    CREATE TABLE IF NOT EXISTS trash (
+        project TEXT NOT NULL REFERENCES projects(name) ON DELETE CASCADE,
+        id INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (project, id)
+    );
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS events (
+        seq INTEGER PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS history (
+        id INTEGER PRIMARY KEY,
+        stack TEXT NOT NULL,
+        schema_version INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+";
+
+/// Embedded SQLite database. Saving only writes the rows that changed
+/// instead of rewriting the whole store, and logs the events in the same
+/// transaction, so the tables always hold the current state.
+pub struct SqliteStorage {
+    path: PathBuf,
+}
+
+impl SqliteStorage {
+    pub fn new(path: PathBuf) -> Self {
+        SqliteStorage { path }
+    }
+
+    fn error(&self, message: impl ToString) -> StorageError {
+        StorageError::Database {
+            path: self.path.clone(),
+            message: message.to_string(),
+        }
+    }
+
+    fn connect(&self) -> Result<Connection, StorageError> {
+        if let Some(dir) = self.path.parent().filter(|x| !x.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| self.error(e))?;
+        }
+
+        let connection = Connection::open(&self.path).map_err(|e| self.error(e))?;
+        connection
+            .execute_batch("PRAGMA foreign_keys = ON;")
+            .and_then(|_| connection.execute_batch(SCHEMA))
+            .map_err(|e| self.error(e))?;
+
+        Ok(connection)
+    }
+
+    /// Every row of `table` with its project, in list order
+    fn read_rows<T: DeserializeOwned>(
+        &self,
+        connection: &Connection,
+        table: &str,
+    ) -> Result<Vec<(String, T)>, StorageError> {
+        let db = |e: rusqlite::Error| self.error(e);
+        let sql = format!(
+            "SELECT project, id, data FROM {} ORDER BY project, position",
+            table
+        );
+
+        let mut statement = connection.prepare(&sql).map_err(db)?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .map_err(db)?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let (project, id, data): (String, u64, String) = row.map_err(db)?;
+            let value = serde_json::from_str(&data)
+                .map_err(|e| self.error(format!("todo {} in '{}': {}", id, project, e)))?;
+            result.push((project, value));
+        }
+
+        Ok(result)
+    }
+
+    /// Makes the project's rows in `table` match `rows`, writing only those
+    /// that changed
+    fn write_rows<'a, T: Serialize + 'a>(
+        &self,
+        transaction: &Transaction,
+        table: &str,
+        project: &str,
+        rows: impl Iterator<Item = (u64, &'a T)>,
+    ) -> Result<(), StorageError> {
+        let db = |e: rusqlite::Error| self.error(e);
+        let upsert = format!(
+            "INSERT INTO {} (project, id, position, data) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (project, id) DO UPDATE
+             SET position = excluded.position, data = excluded.data
+             WHERE position IS NOT excluded.position OR data IS NOT excluded.data",
+            table
+        );
+
+        let mut ids = HashSet::new();
+        for (position, (id, row)) in rows.enumerate() {
+            let data = serde_json::to_string(row).map_err(|e| self.error(e))?;
+            transaction
+                .execute(&upsert, params![project, id, position as u64, data])
+                .map_err(db)?;
+            ids.insert(id);
+        }
+
+        let stored: Vec<u64> = transaction
+            .prepare(&format!("SELECT id FROM {} WHERE project = ?1", table))
+            .and_then(|mut x| x.query_map(params![project], |row| row.get(0))?.collect())
+            .map_err(db)?;
+        let delete = format!("DELETE FROM {} WHERE project = ?1 AND id = ?2", table);
+        for id in stored.into_iter().filter(|x| !ids.contains(x)) {
+            transaction
+                .execute(&delete, params![project, id])
+                .map_err(db)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_store(&self, transaction: &Transaction, store: &Store) -> Result<(), StorageError> {
+        let db = |e: rusqlite::Error| self.error(e);
+
+        let stored: Vec<String> = transaction
+            .prepare("SELECT name FROM projects")
+            .and_then(|mut x| x.query_map([], |row| row.get(0))?.collect())
+            .map_err(db)?;
+        for name in stored {
+            if store.project(&name).is_err() {
+                transaction
+                    .execute("DELETE FROM projects WHERE name = ?1", params![name])
+                    .map_err(db)?;
+            }
+        }
+
+        for (name, list) in store.projects() {
+            transaction
+                .execute(
+                    "INSERT INTO projects (name, next_id) VALUES (?1, ?2)
+                     ON CONFLICT (name) DO UPDATE SET next_id = excluded.next_id
+                     WHERE next_id IS NOT excluded.next_id",
+                    params![name, list.next_id()],
+                )
+                .map_err(db)?;
+
+            let items = list.items.iter().map(|x| (x.id, x));
+            self.write_rows(transaction, "todos", name, items)?;
+            let trash = list.trash.iter().map(|x| (x.todo.id, x));
+            self.write_rows(transaction, "trash", name, trash)?;
+        }
+
+        transaction
+            .execute(
+                "INSERT INTO meta (key, value) VALUES ('event_seq', ?1)
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                params![store.seq()],
+            )
+            .map_err(db)?;
+
+        Ok(())
+    }
+
+    fn write_events(
+        &self,
+        transaction: &Transaction,
+        events: &[Event],
+    ) -> Result<(), StorageError> {
+        for event in events {
+            let data = serde_json::to_string(event).map_err(|e| self.error(e))?;
+            transaction
+                .execute(
+                    "INSERT OR IGNORE INTO events (seq, data) VALUES (?1, ?2)",
+                    params![event.seq, data],
+                )
+                .map_err(|e| self.error(e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves a JSON lines event log left next to the database by older
+    /// versions into the `events` table, applying what the tables missed
+    fn import_log(&self) -> Result<(), StorageError> {
+        let log = file::events_path(&self.path);
+        if !log.exists() {
+            return Ok(());
+        }
+
+        let events = file::load_events(&self.path, 0)?;
+        let mut store = self.load_snapshot()?;
+        let seq = store.seq();
+        for event in events.iter().filter(|x| x.seq > seq) {
+            event.apply(&mut store);
+        }
+
+        let mut connection = self.connect()?;
+        let transaction = connection.transaction().map_err(|e| self.error(e))?;
+        self.write_events(&transaction, &events)?;
+        self.write_store(&transaction, &store)?;
+        transaction.commit().map_err(|e| self.error(e))?;
+
+        fs::remove_file(&log).map_err(|e| self.error(e))
+    }
+
+    /// Makes the `history` table match `history`, writing only the entries
+    /// that changed, which is usually the one just recorded, undone or redone
+    fn write_history(
+        &self,
+        transaction: &Transaction,
+        history: &History,
+    ) -> Result<(), StorageError> {
+        let db = |e: rusqlite::Error| self.error(e);
+        let entries = (history.undo_entries().iter().map(|x| ("undo", x)))
+            .chain(history.redo_entries().iter().map(|x| ("redo", x)));
+
+        let mut ids = HashSet::new();
+        for (stack, entry) in entries {
+            let data = serde_json::to_string(entry).map_err(|e| self.error(e))?;
+            transaction
+                .execute(
+                    "INSERT INTO history (id, stack, schema_version, data)
+                     VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT (id) DO UPDATE
+                     SET stack = excluded.stack, schema_version = excluded.schema_version,
+                         data = excluded.data
+                     WHERE stack IS NOT excluded.stack OR data IS NOT excluded.data
+                         OR schema_version IS NOT excluded.schema_version",
+                    params![entry.id, stack, migrate::SCHEMA_VERSION, data],
+                )
+                .map_err(db)?;
+            ids.insert(entry.id);
+        }
+
+        let stored: Vec<u64> = transaction
+            .prepare("SELECT id FROM history")
+            .and_then(|mut x| x.query_map([], |row| row.get(0))?.collect())
+            .map_err(db)?;
+        for id in stored.into_iter().filter(|x| !ids.contains(x)) {
+            transaction
+                .execute("DELETE FROM history WHERE id = ?1", params![id])
+                .map_err(db)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves an undo history left next to the database by older versions
+    /// into the `history` table
+    fn import_history(&self) -> Result<(), StorageError> {
+        if !file::history_exists(&self.path) {
+            return Ok(());
+        }
+
+        let history = file::load_history(&self.path, || {
+            Ok::<_, StorageError>((self.load()?, self.load_archive()?))
+        })?;
+
+        let mut connection = self.connect()?;
+        let transaction = connection.transaction().map_err(|e| self.error(e))?;
+        self.write_history(&transaction, &history)?;
+        transaction.commit().map_err(|e| self.error(e))?;
+
+        Ok(file::remove_history(&self.path)?)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load_snapshot(&self) -> Result<Store, StorageError> {
+        let connection = self.connect()?;
+        let db = |e: rusqlite::Error| self.error(e);
+
+        let mut projects: BTreeMap<String, (u64, Vec<Todo>, Vec<Trashed>)> = BTreeMap::new();
+        let mut statement = connection
+            .prepare("SELECT name, next_id FROM projects")
+            .map_err(db)?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db)?;
+        for row in rows {
+            let (name, next_id): (String, u64) = row.map_err(db)?;
+            projects.insert(name, (next_id, Vec::new(), Vec::new()));
+        }
+
+        let seq: u64 = connection
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'event_seq'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db)?
+            .unwrap_or(0);
+
+        if projects.is_empty() {
+            let mut store = Store::new();
+            store.set_seq(seq);
+            return Ok(store);
+        }
+
+        for (project, todo) in self.read_rows::<Todo>(&connection, "todos")? {
+            if let Some((_, items, _)) = projects.get_mut(&project) {
+                items.push(todo);
+            }
+        }
+        for (project, entry) in self.read_rows::<Trashed>(&connection, "trash")? {
+            if let Some((_, _, trash)) = projects.get_mut(&project) {
+                trash.push(entry);
+            }
+        }
+
+        let mut store: Store = projects
+            .into_iter()
+            .map(|(name, (next_id, items, trash))| {
+                (name, TodoList::from_parts(items, trash, next_id))
+            })
+            .collect();
+        store.set_seq(seq);
+
+        Ok(store)
+    }
+
+    fn save_snapshot(&self, store: &Store) -> Result<(), StorageError> {
+        let mut connection = self.connect()?;
+        let transaction = connection.transaction().map_err(|e| self.error(e))?;
+        self.write_store(&transaction, store)?;
+
+        transaction.commit().map_err(|e| self.error(e))
+    }
+
+    fn load(&self) -> Result<Store, StorageError> {
+        self.import_log()?;
+        self.load_snapshot()
+    }
+
+    fn save(&self, store: &Store) -> Result<(), StorageError> {
+        let before = self.load()?;
+        let events = events::between(&before, store);
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut connection = self.connect()?;
+        let db = |e: rusqlite::Error| self.error(e);
+        let transaction = connection.transaction().map_err(db)?;
+        self.write_events(&transaction, &events)?;
+        let mut store = store.clone();
+        store.set_seq(before.seq() + events.len() as u64);
+        self.write_store(&transaction, &store)?;
+
+        transaction.commit().map_err(db)
+    }
+
+    fn load_history(&self) -> Result<History, StorageError> {
+        self.import_history()?;
+
+        let connection = self.connect()?;
+        let db = |e: rusqlite::Error| self.error(e);
+        let mut statement = connection
+            .prepare("SELECT id, stack, schema_version, data FROM history ORDER BY id")
+            .map_err(db)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .map_err(db)?;
+
+        let (mut undo, mut redo) = (Vec::new(), Vec::new());
+        for row in rows {
+            let (id, stack, version, data): (u64, String, u32, String) = row.map_err(db)?;
+            let entry = serde_json::from_str(&data)
+                .map_err(|e| e.to_string())
+                .and_then(|x| Entry::parse(x, version))
+                .map_err(|e| self.error(format!("history entry {}: {}", id, e)))?;
+            match stack.as_str() {
+                "undo" => undo.push(entry),
+                _ => redo.push(entry),
+            }
+        }
+        // The next entry to redo has the lowest id
+        redo.reverse();
+
+        Ok(History::from_entries(undo, redo))
+    }
+
+    fn save_history(&self, history: &History) -> Result<(), StorageError> {
+        let mut connection = self.connect()?;
+        let transaction = connection.transaction().map_err(|e| self.error(e))?;
+        self.write_history(&transaction, history)?;
+        transaction.commit().map_err(|e| self.error(e))
+    }
+
+    fn events(&self, since: u64) -> Result<Vec<Event>, StorageError> {
+        let connection = self.connect()?;
+        let db = |e: rusqlite::Error| self.error(e);
+
+        let mut statement = connection
+            .prepare("SELECT seq, data FROM events WHERE seq > ?1 ORDER BY seq")
+            .map_err(db)?;
+        let rows = statement
+            .query_map(params![since], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db)?;
+
+        let mut events = Vec::new();
+        for row in rows {
+            let (seq, data): (u64, String) = row.map_err(db)?;
+            let event =
+                Event::parse(&data).map_err(|e| self.error(format!("event {}: {}", seq, e)))?;
+            events.push(event);
+        }
+
+        Ok(events)
+    }
+}
@@ -0,0 +1,204 @@
+use super::events::{self, Event, SNAPSHOT_INTERVAL};
+use super::file::{self, FileError, FileLock, Stamp};
+use super::history::History;
+use super::store::Store;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Where the store is persisted, chosen with `"backend"` in the config file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Json,
+    /// Needs the `sqlite` cargo feature
+    Sqlite,
+}
+
+impl Backend {
+    pub fn default_file(&self) -> &'static str {
+        match self {
+            Backend::Json => "todo.json",
+            Backend::Sqlite => "todo.db",
+        }
+    }
+}
+
+/// Persistence used by both binaries. Callers hold `lock` from load to save
+/// and compare `stamp`s to notice writes made by other processes.
+///
+/// Every change is also recorded as an `Event`. The default methods below
+/// keep the log in a JSON lines file next to the store, and the backend only
+/// holds a snapshot that is refreshed every `SNAPSHOT_INTERVAL` events; the
+/// current state is that snapshot plus the events logged after it. Backends
+/// that can update the store in place, like SQLite, keep the log themselves
+/// and override `load`, `save` and `events`, as well as `load_history` and
+/// `save_history` to keep the undo history with the store. The archive is a
+/// JSON file whatever the backend.
+pub trait Storage: Send + Sync {
+    fn path(&self) -> &Path;
+
+    fn load_snapshot(&self) -> Result<Store, StorageError>;
+
+    fn save_snapshot(&self, store: &Store) -> Result<(), StorageError>;
+
+    fn load(&self) -> Result<Store, StorageError> {
+        let mut store = self.load_snapshot()?;
+        for event in file::load_recent_events(self.path(), store.seq())? {
+            event.apply(&mut store);
+        }
+
+        Ok(store)
+    }
+
+    /// Logs the changes from the stored state to `store`. Once a snapshot is
+    /// written, the events it covers are rotated out of the log that `load`
+    /// reads, so neither grows past `SNAPSHOT_INTERVAL` events.
+    fn save(&self, store: &Store) -> Result<(), StorageError> {
+        let before = self.load()?;
+        let seq = before.seq();
+
+        let events = events::between(&before, store);
+        if events.is_empty() {
+            return Ok(());
+        }
+        file::append_events(self.path(), &events)?;
+
+        let last = seq + events.len() as u64;
+        if last / SNAPSHOT_INTERVAL != seq / SNAPSHOT_INTERVAL || !self.path().exists() {
+            let mut snapshot = store.clone();
+            snapshot.set_seq(last);
+            self.save_snapshot(&snapshot)?;
+            file::rotate_events(self.path())?;
+        }
+
+        Ok(())
+    }
+
+    fn events(&self, since: u64) -> Result<Vec<Event>, StorageError> {
+        Ok(file::load_events(self.path(), since)?)
+    }
+
+    fn lock(&self) -> Result<FileLock, StorageError> {
+        Ok(file::lock(self.path())?)
+    }
+
+    fn stamp(&self) -> Option<Stamp> {
+        file::stamp(self.path())
+    }
+
+    fn load_history(&self) -> Result<History, StorageError> {
+        file::load_history(self.path(), || Ok((self.load()?, self.load_archive()?)))
+    }
+
+    fn save_history(&self, history: &History) -> Result<(), StorageError> {
+        Ok(file::save_history(self.path(), history)?)
+    }
+
+    fn load_archive(&self) -> Result<Store, StorageError> {
+        Ok(file::load_todos(&file::archive_path(self.path()))?)
+    }
+
+    fn save_archive(&self, archive: &Store) -> Result<(), StorageError> {
+        Ok(file::save_todos(&file::archive_path(self.path()), archive)?)
+    }
+
+    /// Moves done todos completed before `before` from `project`, or every
+    /// project, into the archive and saves both, returning how many moved.
+    /// The move is recorded in the undo history.
+    fn archive(
+        &self,
+        store: &mut Store,
+        project: Option<&str>,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<usize, StorageError> {
+        let mut updated = store.clone();
+        let taken = updated.take_done(project, before);
+        let count = taken.iter().map(|(_, todos)| todos.len()).sum();
+        if count == 0 {
+            return Ok(0);
+        }
+
+        // Loaded while the stores are as the history left them
+        let mut history = self.load_history()?;
+
+        // The archive is written first, so a failure leaves todos in both
+        // stores rather than in neither
+        let mut archive = self.load_archive()?;
+        let before = archive.clone();
+        archive.put_archived(taken);
+        self.save_archive(&archive)?;
+        self.save(&updated)?;
+
+        let operation = format!(
+            "archive {} todo{}",
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+        history.record_archive(operation, (store, &updated), (&before, &archive));
+        *store = updated;
+        self.save_history(&history)?;
+
+        Ok(count)
+    }
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    File(FileError),
+    Database { path: PathBuf, message: String },
+    Unsupported(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::File(e) => write!(f, "{}", e),
+            StorageError::Database { path, message } => {
+                write!(f, "{}: {}", path.display(), message)
+            }
+            StorageError::Unsupported(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<FileError> for StorageError {
+    fn from(error: FileError) -> Self {
+        StorageError::File(error)
+    }
+}
+
+pub fn open(backend: Backend, path: PathBuf) -> Result<Box<dyn Storage>, StorageError> {
+    match backend {
+        Backend::Json => Ok(Box::new(JsonStorage { path })),
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => Ok(Box::new(super::sqlite::SqliteStorage::new(path))),
+        #[cfg(not(feature = "sqlite"))]
+        Backend::Sqlite => Err(StorageError::Unsupported(String::from(
+            "SQLite storage is not available, rebuild with `--features sqlite`",
+        ))),
+    }
+}
+
+/// The whole store as one pretty printed JSON file, see `shared::file`
+pub struct JsonStorage {
+    pub path: PathBuf,
+}
+
+impl Storage for JsonStorage {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load_snapshot(&self) -> Result<Store, StorageError> {
+        Ok(file::load_todos(&self.path)?)
+    }
+
+    fn save_snapshot(&self, store: &Store) -> Result<(), StorageError> {
+        Ok(file::save_todos(&self.path, store)?)
+    }
+}
@@ -0,0 +1,168 @@
+use super::todo::{Todo, TodoList};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const DEFAULT_PROJECT: &str = "default";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Store {
+    projects: BTreeMap<String, TodoList>,
+    // Last event of the log included in this store, see `shared::events`
+    #[serde(default, rename = "event_seq")]
+    seq: u64,
+}
+
+impl Store {
+    pub fn new() -> Store {
+        Store::from(TodoList::new())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.projects.keys()
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn set_seq(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    pub fn projects(&self) -> impl Iterator<Item = (&String, &TodoList)> {
+        self.projects.iter()
+    }
+
+    pub fn project(&self, name: &str) -> Result<&TodoList, String> {
+        self.projects
+            .get(name)
+            .ok_or_else(|| format!("Project '{}' is not found", name))
+    }
+
+    pub fn project_mut(&mut self, name: &str) -> Result<&mut TodoList, String> {
+        // The default project always exists, even in stores that never used it
+        if name == DEFAULT_PROJECT {
+            return Ok(self.projects.entry(name.to_string()).or_default());
+        }
+
+        self.projects
+            .get_mut(name)
+            .ok_or_else(|| format!("Project '{}' is not found", name))
+    }
+
+    pub fn create(&mut self, name: &str) -> Result<(), String> {
+        let name = validate_name(name)?;
+
+        if self.projects.contains_key(name) {
+            return Err(format!("Project '{}' already exists", name));
+        }
+
+        self.projects.insert(name.to_string(), TodoList::new());
+        Ok(())
+    }
+
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+        let to = validate_name(to)?;
+
+        if from == DEFAULT_PROJECT {
+            return Err(String::from("The default project cannot be renamed"));
+        }
+        if self.projects.contains_key(to) {
+            return Err(format!("Project '{}' already exists", to));
+        }
+
+        let list = self
+            .projects
+            .remove(from)
+            .ok_or_else(|| format!("Project '{}' is not found", from))?;
+        self.projects.insert(to.to_string(), list);
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<TodoList, String> {
+        if name == DEFAULT_PROJECT {
+            return Err(String::from("The default project cannot be deleted"));
+        }
+
+        self.projects
+            .remove(name)
+            .ok_or_else(|| format!("Project '{}' is not found", name))
+    }
+
+    /// Purges todos deleted before `before` from every project's trash,
+    /// returning how many were purged
+    pub fn purge_trash(&mut self, before: DateTime<Utc>) -> usize {
+        self.projects
+            .values_mut()
+            .map(|x| x.purge(Some(before)))
+            .sum()
+    }
+
+    /// Takes done todos completed before `before` out of `project`, or of
+    /// every project, see `TodoList::take_done`
+    pub fn take_done(
+        &mut self,
+        project: Option<&str>,
+        before: Option<DateTime<Utc>>,
+    ) -> Vec<(String, Vec<Todo>)> {
+        self.projects
+            .iter_mut()
+            .filter(|(name, _)| project.is_none_or(|x| x == name.as_str()))
+            .map(|(name, list)| (name.clone(), list.take_done(before)))
+            .filter(|(_, todos)| !todos.is_empty())
+            .collect()
+    }
+
+    /// Adds todos taken out of another store, creating their projects
+    pub fn put_archived(&mut self, taken: Vec<(String, Vec<Todo>)>) {
+        for (name, todos) in taken {
+            self.projects.entry(name).or_default().put_archived(todos);
+        }
+    }
+
+    // Returns the id the todo got in the target project
+    pub fn move_todo(&mut self, from: &str, id: u64, to: &str) -> Result<u64, String> {
+        if from == to {
+            return Err(format!("Todo {} is already in project '{}'", id, to));
+        }
+        self.project_mut(to)?;
+
+        let todo = self.project_mut(from)?.take(id)?;
+        Ok(self.project_mut(to)?.insert(todo))
+    }
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<TodoList> for Store {
+    fn from(list: TodoList) -> Self {
+        Store {
+            projects: BTreeMap::from([(DEFAULT_PROJECT.to_string(), list)]),
+            seq: 0,
+        }
+    }
+}
+
+impl FromIterator<(String, TodoList)> for Store {
+    fn from_iter<T: IntoIterator<Item = (String, TodoList)>>(iter: T) -> Self {
+        Store {
+            projects: iter.into_iter().collect(),
+            seq: 0,
+        }
+    }
+}
+
+fn validate_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+
+    if name.is_empty() || name.contains('/') {
+        return Err(format!("Invalid project name '{}'", name));
+    }
+
+    Ok(name)
+}
@@ -1,17 +1,91 @@
+use super::recurrence::Recurrence;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, Utc};
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TodoList {
     // true = todo, false = done
     pub items: Vec<Todo>,
     next_id: u64,
+    // Deleted todos, kept by id until restored or purged
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trash: Vec<Trashed>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trashed {
+    #[serde(flatten)]
+    pub todo: Todo,
+    pub deleted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Todo {
     pub id: u64,
     pub title: String,
-    pub done: bool,
+    // Stored as both `status` and the older `done` flag, see `stored_status`
+    #[serde(flatten, with = "stored_status")]
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Priority::is_none")]
+    pub priority: Priority,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<u64>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub blocked_by: BTreeSet<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+    // Missing in files written before timestamps were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+// Declared from highest to lowest so that ascending order puts high priority first
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+    #[default]
+    None,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    #[default]
+    Open,
+    InProgress,
+    Blocked,
+    Waiting,
+    Done,
+    Cancelled,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Priority,
+    Id,
+    Created,
+    Updated,
+    Completed,
 }
 
 impl TodoList {
@@ -19,14 +93,60 @@ impl TodoList {
         TodoList {
             items: Vec::new(),
             next_id: 1,
+            trash: Vec::new(),
         }
     }
 
+    /// Rebuilds a list read back from storage
+    pub fn from_parts(items: Vec<Todo>, trash: Vec<Trashed>, next_id: u64) -> TodoList {
+        TodoList {
+            items,
+            next_id,
+            trash,
+        }
+    }
+
+    /// Adds or replaces a todo exactly as given, keeping its id; used when
+    /// replaying the event log
+    pub fn put(&mut self, todo: Todo) {
+        self.next_id = self.next_id.max(todo.id + 1);
+
+        // Kept in id order, as todos are added
+        match self.items.iter_mut().find(|x| x.id == todo.id) {
+            Some(existing) => *existing = todo,
+            None => {
+                let index = self.items.partition_point(|x| x.id < todo.id);
+                self.items.insert(index, todo);
+            }
+        }
+    }
+
+    /// Id the next added todo will get
+    pub fn next_id(&self) -> u64 {
+        self.next_id
+    }
+
+    /// Only for replaying logged changes, which record it
+    pub fn set_next_id(&mut self, next_id: u64) {
+        self.next_id = next_id;
+    }
+
     pub fn add(&mut self, title: String) -> Todo {
+        let now = Utc::now();
         let todo = Todo {
             id: self.next_id,
             title,
-            done: false,
+            status: Status::Open,
+            due: None,
+            priority: Priority::None,
+            tags: BTreeSet::new(),
+            notes: String::new(),
+            parent: None,
+            blocked_by: BTreeSet::new(),
+            recurrence: None,
+            created_at: Some(now),
+            updated_at: Some(now),
+            completed_at: None,
         };
         self.next_id += 1;
         self.items.push(todo.clone());
@@ -34,22 +154,310 @@ impl TodoList {
         todo
     }
 
+    pub fn add_child(&mut self, parent: u64, title: String) -> Result<Todo, String> {
+        if self.get(parent).is_none() {
+            return Err(format!("Todo {} not found", parent));
+        }
+
+        let id = self.add(title).id;
+        let todo = self.items.iter_mut().find(|x| x.id == id).unwrap();
+        todo.parent = Some(parent);
+
+        Ok(todo.clone())
+    }
+
+    // Refuses to complete a todo while any of its blockers is still open
     pub fn mark(&mut self, id: u64, value: bool) -> Result<(), String> {
+        if self.get(id).is_none() {
+            return Err(format!("Todo '{}' is not found.", id));
+        }
+
+        let blockers = self.open_blockers(id);
+        if value && !blockers.is_empty() {
+            return Err(blocked_message(id, &blockers));
+        }
+
+        self.force_mark(id, value)
+    }
+
+    pub fn force_mark(&mut self, id: u64, value: bool) -> Result<(), String> {
+        let status = if value { Status::Done } else { Status::Open };
+        self.apply_status(id, status)
+    }
+
+    // Moving to done goes through `mark` so open blockers are respected, and
+    // is refused while subtasks are open, which `mark_tree` completes instead
+    pub fn set_status(&mut self, id: u64, status: Status) -> Result<(), String> {
+        if status == Status::Done {
+            let open = self.open_descendants(id);
+            if !open.is_empty() {
+                return Err(format!(
+                    "Todo {} has {} open subtasks, mark them done first",
+                    id,
+                    open.len()
+                ));
+            }
+            return self.mark(id, true);
+        }
+
+        self.apply_status(id, status)
+    }
+
+    fn apply_status(&mut self, id: u64, status: Status) -> Result<(), String> {
         let todo = self
             .items
             .iter_mut()
             .find(|x| x.id == id)
             .ok_or_else(|| format!("Todo '{}' is not found.", id))?;
-        todo.done = value;
+
+        if todo.status == status {
+            return Ok(());
+        }
+
+        // Worked out first so that a date out of range leaves the todo as is
+        let next_due = match &todo.recurrence {
+            Some(recurrence) if !todo.is_done() && status == Status::Done => {
+                let from = todo.due.unwrap_or_else(|| Local::now().date_naive());
+                Some(recurrence.next(from)?)
+            }
+            _ => None,
+        };
+
+        let was_done = todo.is_done();
+        todo.status = status;
+        if !todo.is_done() {
+            todo.completed_at = None;
+        } else if !was_done {
+            todo.completed_at = Some(Utc::now());
+        }
+        todo.touch();
+
+        // Completing a recurring todo hands its rule over to the next occurrence
+        if let Some(due) = next_due
+            && let Some(recurrence) = todo.recurrence.take()
+        {
+            let mut next = todo.clone();
+            next.status = Status::Open;
+            next.due = Some(due);
+            next.recurrence = Some(recurrence);
+            next.blocked_by.clear();
+            next.created_at = Some(Utc::now());
+            next.updated_at = next.created_at;
+            next.completed_at = None;
+
+            next.id = self.next_id;
+            self.next_id += 1;
+            self.items.push(next);
+        }
+
         Ok(())
     }
 
+    // Marks the todo and all of its subtasks, returns the ids that changed.
+    // Blockers inside the tree don't count since they are completed together.
+    pub fn mark_tree(&mut self, id: u64, value: bool) -> Result<Vec<u64>, String> {
+        self.mark_trees(&[id], value)
+    }
+
+    // Like `mark_tree` for several todos at once, where blockers anywhere
+    // among them or their subtasks don't count either
+    pub fn mark_trees(&mut self, ids: &[u64], value: bool) -> Result<Vec<u64>, String> {
+        let ids = self.unmarked_trees(ids, value);
+
+        if value {
+            for id in &ids {
+                let blockers: Vec<u64> = self
+                    .open_blockers(*id)
+                    .into_iter()
+                    .filter(|x| !ids.contains(x))
+                    .collect();
+                if !blockers.is_empty() {
+                    return Err(blocked_message(*id, &blockers));
+                }
+            }
+        }
+
+        for id in &ids {
+            self.force_mark(*id, value)?;
+        }
+
+        Ok(ids)
+    }
+
+    // Like `mark_tree`, ignoring open blockers anywhere in the tree
+    pub fn force_mark_tree(&mut self, id: u64, value: bool) -> Result<Vec<u64>, String> {
+        self.force_mark_trees(&[id], value)
+    }
+
+    pub fn force_mark_trees(&mut self, ids: &[u64], value: bool) -> Result<Vec<u64>, String> {
+        let ids = self.unmarked_trees(ids, value);
+        for id in &ids {
+            self.force_mark(*id, value)?;
+        }
+
+        Ok(ids)
+    }
+
+    fn unmarked_trees(&self, ids: &[u64], value: bool) -> Vec<u64> {
+        let mut all = Vec::new();
+        for id in ids {
+            for x in self.unmarked_tree(*id, value) {
+                if !all.contains(&x) {
+                    all.push(x);
+                }
+            }
+        }
+
+        all
+    }
+
+    fn unmarked_tree(&self, id: u64, value: bool) -> Vec<u64> {
+        let mut ids = vec![id];
+        ids.extend(self.descendants(id));
+        ids.retain(|x| self.get(*x).is_some_and(|x| x.is_done() != value));
+        ids
+    }
+
+    pub fn add_blocker(&mut self, id: u64, blocker: u64) -> Result<bool, String> {
+        if self.get(blocker).is_none() {
+            return Err(format!("Todo {} not found", blocker));
+        }
+        if id == blocker || self.depends_on(blocker, id) {
+            return Err(format!(
+                "Todo {} blocking {} would create a dependency cycle",
+                blocker, id
+            ));
+        }
+
+        let todo = self
+            .items
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or_else(|| format!("Todo {} not found", id))?;
+
+        let changed = todo.blocked_by.insert(blocker);
+        if changed {
+            todo.touch();
+        }
+        Ok(changed)
+    }
+
+    pub fn remove_blocker(&mut self, id: u64, blocker: u64) -> Result<bool, String> {
+        let todo = self
+            .items
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or_else(|| format!("Todo {} not found", id))?;
+
+        let changed = todo.blocked_by.remove(&blocker);
+        if changed {
+            todo.touch();
+        }
+        Ok(changed)
+    }
+
+    pub fn open_blockers(&self, id: u64) -> Vec<u64> {
+        self.get(id)
+            .map(|x| {
+                x.blocked_by
+                    .iter()
+                    .copied()
+                    .filter(|b| self.get(*b).is_some_and(|b| !b.is_done()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn blocked(&self) -> impl Iterator<Item = &Todo> {
+        self.todo()
+            .filter(|x| x.status == Status::Blocked || !self.open_blockers(x.id).is_empty())
+    }
+
+    pub fn ready(&self) -> impl Iterator<Item = &Todo> {
+        self.todo().filter(|x| {
+            !matches!(x.status, Status::Blocked | Status::Waiting)
+                && self.open_blockers(x.id).is_empty()
+        })
+    }
+
+    pub fn with_status(&self, status: Status) -> impl Iterator<Item = &Todo> {
+        self.items.iter().filter(move |x| x.status == status)
+    }
+
+    // Whether `id` waits on `other`, directly or through other blockers
+    fn depends_on(&self, id: u64, other: u64) -> bool {
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![id];
+
+        while let Some(current) = pending.pop() {
+            if !seen.insert(current) {
+                continue;
+            }
+            let Some(todo) = self.get(current) else {
+                continue;
+            };
+            if todo.blocked_by.contains(&other) {
+                return true;
+            }
+            pending.extend(todo.blocked_by.iter().copied());
+        }
+
+        false
+    }
+
+    pub fn children(&self, id: u64) -> impl Iterator<Item = &Todo> {
+        self.items.iter().filter(move |x| x.parent == Some(id))
+    }
+
+    pub fn descendants(&self, id: u64) -> Vec<u64> {
+        let mut result = Vec::new();
+        let mut pending = vec![id];
+
+        while let Some(current) = pending.pop() {
+            for child in self.children(current) {
+                result.push(child.id);
+                pending.push(child.id);
+            }
+        }
+
+        result
+    }
+
+    pub fn open_descendants(&self, id: u64) -> Vec<u64> {
+        self.descendants(id)
+            .into_iter()
+            .filter(|x| self.get(*x).is_some_and(|x| !x.is_done()))
+            .collect()
+    }
+
     pub fn todo(&self) -> impl Iterator<Item = &Todo> {
-        self.items.iter().filter(|x| !x.done)
+        self.items.iter().filter(|x| !x.is_done())
     }
 
     pub fn done(&self) -> impl Iterator<Item = &Todo> {
-        self.items.iter().filter(|x| x.done)
+        self.items.iter().filter(|x| x.is_done())
+    }
+
+    pub fn overdue(&self, today: NaiveDate) -> impl Iterator<Item = &Todo> {
+        self.todo()
+            .filter(move |x| x.due.is_some_and(|d| d < today))
+    }
+
+    pub fn due_today(&self, today: NaiveDate) -> impl Iterator<Item = &Todo> {
+        self.todo().filter(move |x| x.due == Some(today))
+    }
+
+    pub fn upcoming(&self, today: NaiveDate) -> impl Iterator<Item = &Todo> {
+        self.todo()
+            .filter(move |x| x.due.is_some_and(|d| d > today))
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Todo> {
+        self.items.iter().find(|x| x.id == id)
+    }
+
+    pub fn trashed(&self, id: u64) -> Option<&Trashed> {
+        self.trash.iter().find(|x| x.todo.id == id)
     }
 
     pub fn list(&self) -> &[Todo] {
@@ -64,23 +472,393 @@ impl TodoList {
             .ok_or_else(|| format!("Todo {} not found", id))?;
 
         todo.title = title.to_string();
+        todo.touch();
+        Ok(())
+    }
+
+    pub fn set_due(&mut self, id: u64, due: Option<NaiveDate>) -> Result<(), String> {
+        let todo = self
+            .items
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or_else(|| format!("Todo {} not found", id))?;
+
+        todo.due = due;
+        todo.touch();
+        Ok(())
+    }
+
+    pub fn set_priority(&mut self, id: u64, priority: Priority) -> Result<(), String> {
+        let todo = self
+            .items
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or_else(|| format!("Todo {} not found", id))?;
+
+        todo.priority = priority;
+        todo.touch();
+        Ok(())
+    }
+
+    pub fn set_recurrence(
+        &mut self,
+        id: u64,
+        recurrence: Option<Recurrence>,
+    ) -> Result<(), String> {
+        let todo = self
+            .items
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or_else(|| format!("Todo {} not found", id))?;
+
+        todo.recurrence = recurrence;
+        todo.touch();
+        Ok(())
+    }
+
+    pub fn recurring(&self) -> impl Iterator<Item = &Todo> {
+        self.items.iter().filter(|x| x.recurrence.is_some())
+    }
+
+    pub fn set_notes(&mut self, id: u64, notes: &str) -> Result<(), String> {
+        let todo = self
+            .items
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or_else(|| format!("Todo {} not found", id))?;
+
+        todo.notes = notes.trim().to_string();
+        todo.touch();
         Ok(())
     }
 
+    pub fn add_tag(&mut self, id: u64, tag: &str) -> Result<bool, String> {
+        let tag = normalize_tag(tag)?;
+        let todo = self
+            .items
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or_else(|| format!("Todo {} not found", id))?;
+
+        let changed = todo.tags.insert(tag);
+        if changed {
+            todo.touch();
+        }
+        Ok(changed)
+    }
+
+    pub fn remove_tag(&mut self, id: u64, tag: &str) -> Result<bool, String> {
+        let tag = normalize_tag(tag)?;
+        let todo = self
+            .items
+            .iter_mut()
+            .find(|x| x.id == id)
+            .ok_or_else(|| format!("Todo {} not found", id))?;
+
+        let changed = todo.tags.remove(&tag);
+        if changed {
+            todo.touch();
+        }
+        Ok(changed)
+    }
+
+    /// Moves a todo and its subtasks to the trash
     pub fn remove(&mut self, id: u64) -> Result<(), String> {
+        let mut ids = vec![id];
+        ids.extend(self.descendants(id));
+
+        let parents: Vec<Option<u64>> = ids
+            .iter()
+            .map(|x| self.get(*x).and_then(|x| x.parent))
+            .collect();
+
+        let deleted_at = Utc::now();
+        for (id, parent) in ids.into_iter().zip(parents) {
+            // `take` detaches subtasks, keep the tree so restore can rebuild it
+            let mut todo = self.take(id)?;
+            todo.parent = parent;
+            self.trash.push(Trashed { todo, deleted_at });
+        }
+        self.trash.sort_by_key(|x| x.todo.id);
+
+        Ok(())
+    }
+
+    /// Puts a trashed todo back, along with the subtasks deleted with it
+    pub fn restore(&mut self, id: u64) -> Result<(), String> {
+        let Some(deleted_at) = self.trashed(id).map(|x| x.deleted_at) else {
+            return Err(format!("Todo {} is not in the trash", id));
+        };
+
+        // Subtasks trashed earlier on their own stay in the trash
+        let mut ids = vec![id];
+        let mut index = 0;
+        while index < ids.len() {
+            let parent = ids[index];
+            ids.extend(
+                self.trash
+                    .iter()
+                    .filter(|x| x.todo.parent == Some(parent) && x.deleted_at == deleted_at)
+                    .map(|x| x.todo.id),
+            );
+            index += 1;
+        }
+
+        let (restored, kept) = std::mem::take(&mut self.trash)
+            .into_iter()
+            .partition(|x| ids.contains(&x.todo.id));
+        self.trash = kept;
+
+        for Trashed { mut todo, .. } in restored {
+            if todo.id == id {
+                todo.parent = todo.parent.filter(|x| self.get(*x).is_some());
+            }
+            todo.blocked_by
+                .retain(|x| ids.contains(x) || self.get(*x).is_some());
+            todo.touch();
+            self.put(todo);
+        }
+
+        Ok(())
+    }
+
+    /// Permanently deletes trashed todos deleted before `before`, or all of
+    /// them, returning how many were purged
+    pub fn purge(&mut self, before: Option<DateTime<Utc>>) -> usize {
+        let count = self.trash.len();
+        self.trash
+            .retain(|x| before.is_some_and(|before| x.deleted_at >= before));
+
+        count - self.trash.len()
+    }
+
+    /// Takes out done todos completed before `before`, or all of them, for
+    /// the archive. A todo with open subtasks stays until they are done too.
+    pub fn take_done(&mut self, before: Option<DateTime<Utc>>) -> Vec<Todo> {
+        let candidates: Vec<u64> = self
+            .done()
+            // Todos completed before timestamps were kept only go with no cutoff
+            .filter(|x| match (before, x.completed_at) {
+                (Some(before), Some(completed_at)) => completed_at < before,
+                (Some(_), None) => false,
+                (None, _) => true,
+            })
+            .map(|x| x.id)
+            .collect();
+        let ids: Vec<u64> = candidates
+            .iter()
+            .copied()
+            .filter(|x| self.descendants(*x).iter().all(|x| candidates.contains(x)))
+            .collect();
+        let parents: Vec<Option<u64>> = ids
+            .iter()
+            .map(|x| self.get(*x).and_then(|x| x.parent))
+            .collect();
+
+        let mut taken = Vec::new();
+        for (id, parent) in ids.iter().zip(parents) {
+            let Ok(mut todo) = self.take(*id) else {
+                continue;
+            };
+            todo.parent = parent.filter(|x| ids.contains(x));
+            taken.push(todo);
+        }
+
+        taken
+    }
+
+    /// Adds archived todos, keeping their ids unless one is already taken,
+    /// e.g. after the project was reset
+    pub fn put_archived(&mut self, todos: Vec<Todo>) {
+        for todo in todos {
+            if self.get(todo.id).is_some() {
+                self.insert(todo);
+            } else {
+                self.put(todo);
+            }
+        }
+    }
+
+    pub fn take(&mut self, id: u64) -> Result<Todo, String> {
         let index = self
             .items
             .iter()
             .position(|x| x.id == id)
             .ok_or_else(|| format!("Todo '{}' is not found", id))?;
 
-        self.items.remove(index);
-        Ok(())
+        // Subtasks left behind become top level todos
+        for child in self.items.iter_mut().filter(|x| x.parent == Some(id)) {
+            child.parent = None;
+        }
+        for todo in self.items.iter_mut() {
+            todo.blocked_by.remove(&id);
+        }
+
+        Ok(self.items.remove(index))
+    }
+
+    // Adds a todo taken from another list, giving it a fresh id in this one
+    pub fn insert(&mut self, mut todo: Todo) -> u64 {
+        todo.id = self.next_id;
+        todo.parent = None;
+        todo.blocked_by.clear();
+        todo.touch();
+        self.next_id += 1;
+        self.items.push(todo);
+
+        self.next_id - 1
+    }
+}
+
+impl Default for TodoList {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Todo {
+    // Cancelled todos count as done, they need no further work
+    pub fn is_done(&self) -> bool {
+        matches!(self.status, Status::Done | Status::Cancelled)
+    }
+
+    fn touch(&mut self) {
+        self.updated_at = Some(Utc::now());
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        normalize_tag(tag).is_ok_and(|tag| self.tags.contains(&tag))
+    }
+
     pub fn fmt(&self) -> String {
-        format!("[{}] {}", self.id, self.title)
+        let mut title = self.title.clone();
+        for tag in &self.tags {
+            title.push_str(&format!(" +{}", tag));
+        }
+
+        let mut details = Vec::new();
+
+        if !matches!(self.status, Status::Open | Status::Done) {
+            details.push(self.status.to_string());
+        }
+        if !self.priority.is_none() {
+            details.push(format!("{} priority", self.priority));
+        }
+        if let Some(due) = self.due {
+            details.push(format!("due {}", due));
+        }
+        if let Some(recurrence) = &self.recurrence {
+            details.push(format!("repeats {}", recurrence));
+        }
+
+        if details.is_empty() {
+            format!("[{}] {}", self.id, title)
+        } else {
+            format!("[{}] {} ({})", self.id, title, details.join(", "))
+        }
+    }
+}
+
+pub fn blocked_message(id: u64, blockers: &[u64]) -> String {
+    let blockers: Vec<String> = blockers.iter().map(|x| x.to_string()).collect();
+    format!("Todo {} is blocked by {}", id, blockers.join(", "))
+}
+
+// Dates given for completion cutoffs mean the start of that day, local time
+pub fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map_or_else(Utc::now, |x| x.with_timezone(&Utc))
+}
+
+// Tags are stored without the leading '+' that users may type
+pub fn normalize_tag(tag: &str) -> Result<String, String> {
+    let trimmed = tag.trim();
+    let name = trimmed.strip_prefix('+').unwrap_or(trimmed);
+
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        return Err(format!("Invalid tag '{}'", tag));
+    }
+
+    Ok(name.to_lowercase())
+}
+
+impl Priority {
+    pub fn is_none(&self) -> bool {
+        *self == Priority::None
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Priority::High => "high",
+            Priority::Medium => "medium",
+            Priority::Low => "low",
+            Priority::None => "none",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Status::Open => "open",
+            Status::InProgress => "in progress",
+            Status::Blocked => "blocked",
+            Status::Waiting => "waiting",
+            Status::Done => "done",
+            Status::Cancelled => "cancelled",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+mod stored_status {
+    use super::Status;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct StoredStatus {
+        #[serde(default)]
+        done: bool,
+        #[serde(default)]
+        status: Option<Status>,
+    }
+
+    pub fn serialize<S: Serializer>(status: &Status, serializer: S) -> Result<S::Ok, S::Error> {
+        StoredStatus {
+            done: matches!(status, Status::Done | Status::Cancelled),
+            status: Some(*status),
+        }
+        .serialize(serializer)
+    }
+
+    // Files written before statuses existed only carry `done`
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Status, D::Error> {
+        let stored = StoredStatus::deserialize(deserializer)?;
+
+        Ok(stored.status.unwrap_or(if stored.done {
+            Status::Done
+        } else {
+            Status::Open
+        }))
+    }
+}
+
+impl SortBy {
+    pub fn sort(&self, items: &mut [&Todo]) {
+        match self {
+            SortBy::Priority => items.sort_by_key(|x| (x.priority, x.id)),
+            SortBy::Id => items.sort_by_key(|x| x.id),
+            // Oldest first, todos without the timestamp last
+            SortBy::Created => items.sort_by_key(|x| (x.created_at.is_none(), x.created_at, x.id)),
+            SortBy::Updated => items.sort_by_key(|x| (x.updated_at.is_none(), x.updated_at, x.id)),
+            SortBy::Completed => {
+                items.sort_by_key(|x| (x.completed_at.is_none(), x.completed_at, x.id))
+            }
+        }
     }
 }
@@ -0,0 +1,236 @@
+use actix_web::{App, test, web};
+use chrono::{Duration, NaiveDate, Utc};
+use serde_json::{Value, json};
+use std::path::Path;
+use todo::api;
+use todo::api::state::AppState;
+use todo::shared::config::Config;
+use todo::shared::storage::{self, Backend};
+use todo::shared::store::{DEFAULT_PROJECT, Store};
+use todo::shared::todo::Priority;
+
+// Saves `store` to a todo file in `dir` and serves it like todo-api does
+fn state(dir: &Path, store: Store) -> web::Data<AppState> {
+    configured(dir, store, Config::default())
+}
+
+fn configured(dir: &Path, store: Store, config: Config) -> web::Data<AppState> {
+    let storage = storage::open(Backend::Json, dir.join("todo.json")).unwrap();
+    storage.save(&store).unwrap();
+    web::Data::new(AppState::new(store, storage, config))
+}
+
+fn titles(titles: &[&str]) -> Store {
+    let mut store = Store::new();
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    for title in titles {
+        todos.add(title.to_string());
+    }
+    store
+}
+
+fn done_ids(state: &AppState) -> Vec<u64> {
+    let store = state.lock().unwrap();
+    store
+        .project(DEFAULT_PROJECT)
+        .unwrap()
+        .done()
+        .map(|x| x.id)
+        .collect()
+}
+
+#[actix_web::test]
+async fn batch_marks_done() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = state(dir.path(), titles(&["a", "b", "c"]));
+    let app = test::init_service(App::new().app_data(state.clone()).configure(api::routes)).await;
+
+    let request = test::TestRequest::post()
+        .uri("/todos/batch")
+        .set_json(json!({ "action": "mark-done", "ids": [1, 3] }))
+        .to_request();
+    let response: Value = test::call_and_read_body_json(&app, request).await;
+
+    assert_eq!(response, json!({ "changed": [1, 3] }));
+    assert_eq!(done_ids(&state), vec![1, 3]);
+}
+
+#[actix_web::test]
+async fn batch_changes_nothing_when_one_id_is_blocked() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = titles(&["a", "b", "c", "d"]);
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.add_blocker(3, 4).unwrap();
+    todos.add_blocker(3, 2).unwrap();
+    let state = state(dir.path(), store);
+    let app = test::init_service(App::new().app_data(state.clone()).configure(api::routes)).await;
+
+    let request = test::TestRequest::post()
+        .uri("/todos/batch")
+        .set_json(json!({ "action": "mark-done", "ids": [1, 2, 3] }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert_eq!(response.status(), 409);
+    let body = test::read_body(response).await;
+    assert_eq!(body, "Todo 3 is blocked by 4");
+    assert!(done_ids(&state).is_empty());
+}
+
+#[actix_web::test]
+async fn batch_counts_blockers_of_subtasks_done_later_in_the_batch() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = titles(&["parent"]);
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.add_child(1, String::from("child")).unwrap();
+    todos.add(String::from("blocker"));
+    todos.add_blocker(2, 3).unwrap();
+    let state = state(dir.path(), store);
+    let app = test::init_service(App::new().app_data(state.clone()).configure(api::routes)).await;
+
+    let request = test::TestRequest::post()
+        .uri("/todos/batch")
+        .set_json(json!({ "action": "mark-done", "ids": [1, 3], "cascade": true }))
+        .to_request();
+    let response: Value = test::call_and_read_body_json(&app, request).await;
+
+    assert_eq!(response, json!({ "changed": [1, 2, 3] }));
+    assert_eq!(done_ids(&state), vec![1, 2, 3]);
+}
+
+#[actix_web::test]
+async fn batch_over_a_range_with_a_missing_id_changes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = state(dir.path(), titles(&["a", "b", "c"]));
+    let app = test::init_service(App::new().app_data(state.clone()).configure(api::routes)).await;
+
+    let request = test::TestRequest::post()
+        .uri("/todos/batch")
+        .set_json(json!({ "action": "delete", "ids": (1..=5).collect::<Vec<u64>>() }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert_eq!(response.status(), 404);
+    assert_eq!(test::read_body(response).await, "Todo 4 not found");
+    let store = state.lock().unwrap();
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().items.len(), 3);
+}
+
+#[actix_web::test]
+async fn update_keeps_fields_left_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = titles(&["Buy milk"]);
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.set_notes(1, "Oat, not soy").unwrap();
+    todos
+        .set_due(1, NaiveDate::from_ymd_opt(2030, 1, 2))
+        .unwrap();
+    let state = state(dir.path(), store);
+    let app = test::init_service(App::new().app_data(state.clone()).configure(api::routes)).await;
+
+    let request = test::TestRequest::put()
+        .uri("/todos/1")
+        .set_json(json!({ "priority": "high" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 200);
+
+    let store = state.lock().unwrap();
+    let todo = store.project(DEFAULT_PROJECT).unwrap().get(1).unwrap();
+    assert_eq!(todo.title, "Buy milk");
+    assert_eq!(todo.notes, "Oat, not soy");
+    assert_eq!(todo.due, NaiveDate::from_ymd_opt(2030, 1, 2));
+    assert_eq!(todo.priority, Priority::High);
+    drop(store);
+
+    let request = test::TestRequest::put()
+        .uri("/todos/2")
+        .set_json(json!({}))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 404);
+}
+
+#[actix_web::test]
+async fn status_done_refuses_open_subtasks() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = titles(&["parent"]);
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.add_child(1, String::from("child")).unwrap();
+    let state = state(dir.path(), store);
+    let app = test::init_service(App::new().app_data(state.clone()).configure(api::routes)).await;
+
+    let request = test::TestRequest::post()
+        .uri("/todos/1/status")
+        .set_json(json!({ "status": "done" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert_eq!(response.status(), 409);
+    assert!(done_ids(&state).is_empty());
+}
+
+#[actix_web::test]
+async fn dropping_an_unsaved_guard_rolls_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = state(dir.path(), titles(&["a"]));
+
+    let mut store = state.lock().unwrap();
+    let before = store.clone();
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.add(String::from("b"));
+    todos.mark(1, true).unwrap();
+    drop(store);
+
+    assert_eq!(*state.lock().unwrap(), before);
+}
+
+#[actix_web::test]
+async fn lock_picks_up_changes_written_by_another_process() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = state(dir.path(), titles(&["a"]));
+    drop(state.lock().unwrap());
+
+    let other = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+    let mut store = other.load().unwrap();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add(String::from("b"));
+    other.save(&store).unwrap();
+
+    let store = state.lock().unwrap();
+    let todos = store.project(DEFAULT_PROJECT).unwrap();
+    assert_eq!(todos.items.len(), 2);
+    assert_eq!(todos.get(2).unwrap().title, "b");
+}
+
+#[actix_web::test]
+async fn undo_reverts_auto_archiving_without_archiving_again() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = titles(&["old"]);
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.mark(1, true).unwrap();
+    todos.items[0].completed_at = Some(Utc::now() - Duration::days(3));
+    let config = Config {
+        archive_after_days: 1,
+        ..Config::default()
+    };
+    let state = configured(dir.path(), store, config);
+
+    // Archived as the store is locked
+    let store = state.lock().unwrap();
+    assert!(store.project(DEFAULT_PROJECT).unwrap().items.is_empty());
+    drop(store);
+
+    assert_eq!(state.undo().unwrap(), Some(String::from("archive 1 todo")));
+    assert_eq!(state.undo().unwrap(), None);
+    assert_eq!(state.redo().unwrap(), Some(String::from("archive 1 todo")));
+    assert_eq!(state.undo().unwrap(), Some(String::from("archive 1 todo")));
+    assert_eq!(
+        state
+            .load_archive()
+            .unwrap()
+            .project(DEFAULT_PROJECT)
+            .map(|x| x.items.len()),
+        Ok(0)
+    );
+}
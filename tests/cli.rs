@@ -1,11 +1,21 @@
-use todo::cli::{Command, ListMode, RunResult, run};
-use todo::prompter::Prompter;
-use todo::shared::todo::TodoList;
+use chrono::NaiveDate;
+use todo::cli::{
+    Command, IdList, ListMode, MAX_ID_RANGE, ProjectCommand, RunResult, archive, doctor,
+    import_json, migrate, redo, run, run_project, undo,
+};
+use todo::prompter::{AssumeYes, Prompter};
+use todo::shared::events::SNAPSHOT_INTERVAL;
+use todo::shared::file::{backup_path, events_path, load_todos, save_todos};
+use todo::shared::history::History;
+use todo::shared::storage::{self, Backend};
+use todo::shared::store::{DEFAULT_PROJECT, Store};
+use todo::shared::todo::{Priority, SortBy, Status, TodoList};
 
 struct FakePrompter {
     selection: Option<usize>,
     input: Option<String>,
     confirm: bool,
+    edit: Option<String>,
 }
 
 impl FakePrompter {
@@ -14,6 +24,7 @@ impl FakePrompter {
             selection: None,
             input: None,
             confirm: false,
+            edit: None,
         }
     }
 
@@ -22,6 +33,7 @@ impl FakePrompter {
             selection: Some(0),
             input: None,
             confirm: true,
+            edit: None,
         }
     }
 
@@ -30,6 +42,7 @@ impl FakePrompter {
             selection: Some(0),
             input: None,
             confirm: false,
+            edit: None,
         }
     }
 
@@ -38,6 +51,7 @@ impl FakePrompter {
             selection: Some(0),
             input: Some(String::from("Changed")),
             confirm: false,
+            edit: None,
         }
     }
 
@@ -46,6 +60,16 @@ impl FakePrompter {
             selection: Some(0),
             input: None,
             confirm: false,
+            edit: None,
+        }
+    }
+
+    pub fn select_first_and_edit(text: &str) -> FakePrompter {
+        FakePrompter {
+            selection: Some(0),
+            input: None,
+            confirm: false,
+            edit: Some(String::from(text)),
         }
     }
 }
@@ -55,6 +79,10 @@ impl Prompter for FakePrompter {
         Ok(self.selection)
     }
 
+    fn multi_select(&self, _: &[String], _: &str) -> Result<Option<Vec<usize>>, String> {
+        Ok(self.selection.map(|x| vec![x]))
+    }
+
     fn input(&self, _: &str, _: &str) -> Result<Option<String>, String> {
         Ok(self.input.clone())
     }
@@ -62,6 +90,10 @@ impl Prompter for FakePrompter {
     fn confirm(&self, _: &str) -> Result<bool, String> {
         Ok(self.confirm)
     }
+
+    fn edit(&self, _: &str) -> Result<Option<String>, String> {
+        Ok(self.edit.clone())
+    }
 }
 
 #[test]
@@ -71,6 +103,11 @@ fn add() {
     let result = run(
         Command::Add {
             key: String::from("First task"),
+            due: None,
+            priority: Priority::None,
+            tags: vec![],
+            notes: None,
+            repeat: None,
         },
         &mut todos,
         &FakePrompter::select_first_and_confirm(),
@@ -81,16 +118,126 @@ fn add() {
     assert_eq!(todos.items.len(), 1);
 }
 
+#[test]
+fn add_with_details() {
+    let mut todos = TodoList::new();
+    let due = NaiveDate::from_ymd_opt(2030, 1, 31).unwrap();
+
+    let result = run(
+        Command::Add {
+            key: String::from("First task"),
+            due: Some(due),
+            priority: Priority::High,
+            tags: vec![String::from("+backend")],
+            notes: Some(String::from("Some notes")),
+            repeat: "weekly".parse().ok(),
+        },
+        &mut todos,
+        &FakePrompter::select_first_and_confirm(),
+    )
+    .unwrap();
+
+    assert_changed(&result);
+    assert_eq!(todos.items.first().unwrap().due, Some(due));
+    assert_eq!(todos.items.first().unwrap().priority, Priority::High);
+    assert!(todos.items.first().unwrap().has_tag("backend"));
+    assert_eq!(todos.items.first().unwrap().notes, "Some notes");
+    assert!(todos.items.first().unwrap().recurrence.is_some());
+}
+
+#[test]
+fn add_child() {
+    let mut todos = TodoList::new();
+    todos.add("Parent".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let command = Command::AddChild {
+        key: String::from("Child"),
+    };
+    let result = run(command, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert_eq!(todos.children(1).count(), 1);
+}
+
+#[test]
+fn add_child_no_todo() {
+    let mut todos = TodoList::new();
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let command = Command::AddChild {
+        key: String::from("Child"),
+    };
+    let result = run(command, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+    assert!(todos.items.is_empty());
+}
+
 #[test]
 fn mark_done() {
     let mut todos = TodoList::new();
     todos.add("Test".into());
 
     let prompter = FakePrompter::select_first_and_confirm();
-    let result = run(Command::MarkDone, &mut todos, &prompter).unwrap();
+    let result = run(Command::MarkDone { ids: vec![] }, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert!(todos.items.first().unwrap().is_done());
+}
+
+#[test]
+fn mark_done_with_open_subtasks_confirmed() {
+    let mut todos = TodoList::new();
+    todos.add("Parent".into());
+    let _ = todos.add_child(1, "Child".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = run(Command::MarkDone { ids: vec![] }, &mut todos, &prompter).unwrap();
 
     assert_changed(&result);
-    assert!(todos.items.first().unwrap().done);
+    assert!(todos.items.iter().all(|x| x.is_done()));
+}
+
+#[test]
+fn mark_done_with_open_subtasks_not_confirmed() {
+    let mut todos = TodoList::new();
+    todos.add("Parent".into());
+    let _ = todos.add_child(1, "Child".into());
+
+    let prompter = FakePrompter::select_first_and_not_confirm();
+    let result = run(Command::MarkDone { ids: vec![] }, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+    assert!(todos.items.iter().all(|x| !x.is_done()));
+}
+
+#[test]
+fn mark_done_blocked_confirmed() {
+    let mut todos = TodoList::new();
+    todos.add("Blocker".into());
+    todos.add("Blocked".into());
+    let _ = todos.add_blocker(1, 2);
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = run(Command::MarkDone { ids: vec![] }, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert!(todos.get(1).unwrap().is_done());
+}
+
+#[test]
+fn mark_done_blocked_not_confirmed() {
+    let mut todos = TodoList::new();
+    todos.add("Blocker".into());
+    todos.add("Blocked".into());
+    let _ = todos.add_blocker(1, 2);
+
+    let prompter = FakePrompter::select_first_and_not_confirm();
+    let result = run(Command::MarkDone { ids: vec![] }, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+    assert!(!todos.get(1).unwrap().is_done());
 }
 
 #[test]
@@ -99,10 +246,10 @@ fn mark_done_not_selected() {
     todos.add("Test".into());
 
     let prompter = FakePrompter::nothing_selected();
-    let result = run(Command::MarkDone, &mut todos, &prompter).unwrap();
+    let result = run(Command::MarkDone { ids: vec![] }, &mut todos, &prompter).unwrap();
 
     assert_no_change(&result);
-    assert_eq!(todos.items.first().unwrap().done, false);
+    assert!(!todos.items.first().unwrap().is_done());
 }
 
 #[test]
@@ -110,7 +257,7 @@ fn mark_done_no_todo() {
     let mut todos = TodoList::new();
 
     let prompter = FakePrompter::select_first_and_confirm();
-    let result = run(Command::MarkDone, &mut todos, &prompter).unwrap();
+    let result = run(Command::MarkDone { ids: vec![] }, &mut todos, &prompter).unwrap();
 
     assert_no_change(&result);
 }
@@ -122,10 +269,10 @@ fn undo_done() {
     let _ = todos.mark(1, true);
 
     let prompter = FakePrompter::select_first_and_confirm();
-    let result = run(Command::UndoDone, &mut todos, &prompter).unwrap();
+    let result = run(Command::UndoDone { ids: vec![] }, &mut todos, &prompter).unwrap();
 
     assert_changed(&result);
-    assert_eq!(todos.items.first().unwrap().done, false);
+    assert!(!todos.items.first().unwrap().is_done());
 }
 
 #[test]
@@ -135,10 +282,10 @@ fn undo_done_not_selected() {
     let _ = todos.mark(1, true);
 
     let prompter = FakePrompter::nothing_selected();
-    let result = run(Command::UndoDone, &mut todos, &prompter).unwrap();
+    let result = run(Command::UndoDone { ids: vec![] }, &mut todos, &prompter).unwrap();
 
     assert_no_change(&result);
-    assert!(todos.items.first().unwrap().done);
+    assert!(todos.items.first().unwrap().is_done());
 }
 
 #[test]
@@ -147,7 +294,7 @@ fn undo_done_no_todo() {
     todos.add("Test".into());
 
     let prompter = FakePrompter::select_first_and_confirm();
-    let result = run(Command::UndoDone, &mut todos, &prompter).unwrap();
+    let result = run(Command::UndoDone { ids: vec![] }, &mut todos, &prompter).unwrap();
 
     assert_no_change(&result);
 }
@@ -165,6 +312,13 @@ fn list() {
         &run(
             Command::List {
                 mode: ListMode::All,
+                priority: None,
+                sort: SortBy::Priority,
+                status: None,
+                tags: vec![],
+                filter: None,
+                archived: false,
+                format: None,
             },
             &mut todos,
             &prompter,
@@ -176,6 +330,13 @@ fn list() {
         &run(
             Command::List {
                 mode: ListMode::Todo,
+                priority: None,
+                sort: SortBy::Priority,
+                status: None,
+                tags: vec![],
+                filter: None,
+                archived: false,
+                format: None,
             },
             &mut todos,
             &prompter,
@@ -187,12 +348,46 @@ fn list() {
         &run(
             Command::List {
                 mode: ListMode::Done,
+                priority: None,
+                sort: SortBy::Priority,
+                status: None,
+                tags: vec![],
+                filter: None,
+                archived: false,
+                format: None,
             },
             &mut todos,
             &prompter,
         )
         .unwrap(),
     );
+
+    for mode in [
+        ListMode::Overdue,
+        ListMode::Today,
+        ListMode::Upcoming,
+        ListMode::Blocked,
+        ListMode::Ready,
+        ListMode::Recurring,
+    ] {
+        assert_no_change(
+            &run(
+                Command::List {
+                    mode,
+                    priority: Some(Priority::High),
+                    sort: SortBy::Completed,
+                    status: Some(Status::InProgress),
+                    tags: vec![String::from("backend")],
+                    filter: None,
+                    archived: false,
+                    format: None,
+                },
+                &mut todos,
+                &prompter,
+            )
+            .unwrap(),
+        );
+    }
 }
 
 #[test]
@@ -201,7 +396,15 @@ fn update() {
     todos.add("Test".into());
 
     let prompter = FakePrompter::select_first_and_give_input();
-    let result = run(Command::Update, &mut todos, &prompter).unwrap();
+    let result = run(
+        Command::Update {
+            id: None,
+            title: None,
+        },
+        &mut todos,
+        &prompter,
+    )
+    .unwrap();
 
     assert_changed(&result);
     assert_eq!(todos.items.first().unwrap().title, "Changed");
@@ -213,7 +416,15 @@ fn update_no_input() {
     todos.add("Test".into());
 
     let prompter = FakePrompter::select_first_and_no_input();
-    let result = run(Command::Update, &mut todos, &prompter).unwrap();
+    let result = run(
+        Command::Update {
+            id: None,
+            title: None,
+        },
+        &mut todos,
+        &prompter,
+    )
+    .unwrap();
 
     assert_no_change(&result);
     assert_eq!(todos.items.first().unwrap().title, "Test");
@@ -225,7 +436,15 @@ fn update_not_selected() {
     todos.add("Test".into());
 
     let prompter = FakePrompter::nothing_selected();
-    let result = run(Command::Update, &mut todos, &prompter).unwrap();
+    let result = run(
+        Command::Update {
+            id: None,
+            title: None,
+        },
+        &mut todos,
+        &prompter,
+    )
+    .unwrap();
 
     assert_no_change(&result);
     assert_eq!(todos.items.first().unwrap().title, "Test");
@@ -236,7 +455,15 @@ fn update_no_todo() {
     let mut todos = TodoList::new();
 
     let prompter = FakePrompter::select_first_and_give_input();
-    let result = run(Command::Update, &mut todos, &prompter).unwrap();
+    let result = run(
+        Command::Update {
+            id: None,
+            title: None,
+        },
+        &mut todos,
+        &prompter,
+    )
+    .unwrap();
 
     assert_no_change(&result);
 }
@@ -247,10 +474,78 @@ fn delete_confirmed_removes_item() {
     todos.add("Test".into());
 
     let prompter = FakePrompter::select_first_and_confirm();
-    let result = run(Command::Delete, &mut todos, &prompter).unwrap();
+    let result = run(Command::Delete { ids: vec![] }, &mut todos, &prompter).unwrap();
 
     assert_changed(&result);
     assert!(todos.items.is_empty());
+    assert_eq!(todos.trash.len(), 1);
+}
+
+#[test]
+fn restore_deleted_item() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+    let _ = todos.remove(1);
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = run(Command::Restore, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert_eq!(todos.items.len(), 1);
+    assert!(todos.trash.is_empty());
+}
+
+#[test]
+fn purge_not_confirmed_keeps_trash() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+    let _ = todos.remove(1);
+
+    let prompter = FakePrompter::select_first_and_not_confirm();
+    let result = run(Command::Purge, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+    assert_eq!(todos.trash.len(), 1);
+}
+
+#[test]
+fn delete_by_ids_with_yes() {
+    let mut todos = TodoList::new();
+    todos.add("Parent".into());
+    let _ = todos.add_child(1, "Child".into());
+    todos.add("Other".into());
+
+    let prompter = FakePrompter::nothing_selected();
+    let result = run(
+        Command::Delete {
+            ids: vec!["1-2".parse().unwrap()],
+        },
+        &mut todos,
+        &AssumeYes(&prompter),
+    )
+    .unwrap();
+
+    assert_changed(&result);
+    assert_eq!(todos.items.len(), 1);
+    assert_eq!(todos.trash.len(), 2);
+}
+
+#[test]
+fn delete_unknown_id() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = run(
+        Command::Delete {
+            ids: vec!["1,7".parse().unwrap()],
+        },
+        &mut todos,
+        &prompter,
+    );
+
+    assert_eq!(result, Err(String::from("Todo '7' is not found.")));
+    assert_eq!(todos.items.len(), 1);
 }
 
 #[test]
@@ -259,7 +554,7 @@ fn delete_not_confirmed_dont_removes_item() {
     todos.add("Test".into());
 
     let prompter = FakePrompter::select_first_and_not_confirm();
-    let result = run(Command::Delete, &mut todos, &prompter).unwrap();
+    let result = run(Command::Delete { ids: vec![] }, &mut todos, &prompter).unwrap();
 
     assert_no_change(&result);
     assert_eq!(todos.items.len(), 1);
@@ -271,7 +566,7 @@ fn delete_not_selected() {
     todos.add("Test".into());
 
     let prompter = FakePrompter::nothing_selected();
-    let result = run(Command::Delete, &mut todos, &prompter).unwrap();
+    let result = run(Command::Delete { ids: vec![] }, &mut todos, &prompter).unwrap();
 
     assert_no_change(&result);
     assert_eq!(todos.items.len(), 1);
@@ -282,11 +577,710 @@ fn delete_no_todo() {
     let mut todos = TodoList::new();
 
     let prompter = FakePrompter::select_first_and_confirm();
-    let result = run(Command::Delete, &mut todos, &prompter).unwrap();
+    let result = run(Command::Delete { ids: vec![] }, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+}
+
+#[test]
+fn prioritize() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = run(Command::Prioritize, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert_eq!(todos.items.first().unwrap().priority, Priority::High);
+}
+
+#[test]
+fn prioritize_not_selected() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+
+    let prompter = FakePrompter::nothing_selected();
+    let result = run(Command::Prioritize, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+    assert_eq!(todos.items.first().unwrap().priority, Priority::None);
+}
+
+#[test]
+fn tag() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let command = Command::Tag {
+        tags: vec![String::from("+backend"), String::from("urgent")],
+    };
+    let result = run(command, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert!(todos.items.first().unwrap().has_tag("backend"));
+    assert!(todos.items.first().unwrap().has_tag("urgent"));
+}
+
+#[test]
+fn tag_already_present() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+    let _ = todos.add_tag(1, "backend");
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let command = Command::Tag {
+        tags: vec![String::from("backend")],
+    };
+    let result = run(command, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+}
+
+#[test]
+fn untag() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+    let _ = todos.add_tag(1, "backend");
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let command = Command::Untag {
+        tags: vec![String::from("+backend")],
+    };
+    let result = run(command, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert!(todos.items.first().unwrap().tags.is_empty());
+}
+
+#[test]
+fn untag_no_tagged_todo() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let command = Command::Untag {
+        tags: vec![String::from("backend")],
+    };
+    let result = run(command, &mut todos, &prompter).unwrap();
 
     assert_no_change(&result);
 }
 
+#[test]
+fn block_and_unblock() {
+    let mut todos = TodoList::new();
+    todos.add("Blocked".into());
+    todos.add("Blocker".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = run(Command::Block { blockers: vec![2] }, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert_eq!(todos.open_blockers(1), vec![2]);
+
+    let result = run(
+        Command::Unblock { blockers: vec![2] },
+        &mut todos,
+        &prompter,
+    )
+    .unwrap();
+
+    assert_changed(&result);
+    assert!(todos.open_blockers(1).is_empty());
+}
+
+#[test]
+fn block_cycle() {
+    let mut todos = TodoList::new();
+    todos.add("First".into());
+    todos.add("Second".into());
+    let _ = todos.add_blocker(2, 1);
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = run(Command::Block { blockers: vec![2] }, &mut todos, &prompter);
+
+    assert!(result.is_err());
+    assert!(todos.open_blockers(1).is_empty());
+}
+
+#[test]
+fn repeat_and_stop() {
+    let mut todos = TodoList::new();
+    todos.add("Standup".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let command = Command::Repeat {
+        rule: "daily".parse().unwrap(),
+    };
+    let result = run(command, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert_eq!(todos.recurring().count(), 1);
+
+    let result = run(Command::StopRepeat, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert_eq!(todos.recurring().count(), 0);
+}
+
+#[test]
+fn stop_repeat_no_recurring_todo() {
+    let mut todos = TodoList::new();
+    todos.add("Standup".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = run(Command::StopRepeat, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+}
+
+#[test]
+fn status() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+
+    // Picks the first todo, then the first status in the list
+    let prompter = FakePrompter::select_first_and_confirm();
+    let _ = todos.set_status(1, Status::Waiting);
+    let result = run(Command::Status, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert_eq!(todos.items.first().unwrap().status, Status::Open);
+}
+
+#[test]
+fn status_done_confirms_open_subtasks() {
+    let mut todos = TodoList::new();
+    for title in ["A", "B", "C", "D", "Parent"] {
+        todos.add(title.into());
+    }
+    let _ = todos.add_child(5, "Child".into());
+
+    // Picks the fifth todo, then the fifth status, which is done
+    let mut prompter = FakePrompter::select_first_and_not_confirm();
+    prompter.selection = Some(4);
+    let result = run(Command::Status, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+    assert!(!todos.get(5).unwrap().is_done());
+
+    prompter.confirm = true;
+    let result = run(Command::Status, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert!(todos.get(5).unwrap().is_done());
+    assert!(todos.get(6).unwrap().is_done());
+}
+
+#[test]
+fn status_unchanged() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = run(Command::Status, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+}
+
+#[test]
+fn show() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = run(Command::Show, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+}
+
+#[test]
+fn edit() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+
+    let prompter = FakePrompter::select_first_and_edit(
+        "title: Changed\ndue: 2030-01-31\npriority: high\ntags: +backend\n---\nFirst line\nSecond line\n",
+    );
+    let result = run(Command::Edit, &mut todos, &prompter).unwrap();
+
+    assert_changed(&result);
+    let todo = todos.items.first().unwrap();
+    assert_eq!(todo.title, "Changed");
+    assert_eq!(todo.due, NaiveDate::from_ymd_opt(2030, 1, 31));
+    assert_eq!(todo.priority, Priority::High);
+    assert!(todo.has_tag("backend"));
+    assert_eq!(todo.notes, "First line\nSecond line");
+}
+
+#[test]
+fn edit_unchanged() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+
+    let prompter = FakePrompter::select_first_and_edit("title: Test\n---\n");
+    let result = run(Command::Edit, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+}
+
+#[test]
+fn edit_not_saved() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = run(Command::Edit, &mut todos, &prompter).unwrap();
+
+    assert_no_change(&result);
+}
+
+#[test]
+fn edit_invalid_buffer() {
+    let mut todos = TodoList::new();
+    todos.add("Test".into());
+
+    let prompter = FakePrompter::select_first_and_edit("title: Test\ndue: someday\n---\n");
+    let result = run(Command::Edit, &mut todos, &prompter);
+
+    assert_eq!(
+        result,
+        Err(String::from("Line 2: invalid due date 'someday'"))
+    );
+    assert_eq!(todos.items.first().unwrap().due, None);
+}
+
+#[test]
+fn project_create_and_rename() {
+    let mut store = Store::new();
+    let prompter = FakePrompter::nothing_selected();
+
+    let command = ProjectCommand::Create {
+        name: String::from("work"),
+    };
+    assert_changed(&run_project(command, &mut store, DEFAULT_PROJECT, &prompter).unwrap());
+
+    let command = ProjectCommand::Rename {
+        from: String::from("work"),
+        to: String::from("job"),
+    };
+    assert_changed(&run_project(command, &mut store, DEFAULT_PROJECT, &prompter).unwrap());
+    assert!(store.project("job").is_ok());
+
+    let command = ProjectCommand::List;
+    assert_no_change(&run_project(command, &mut store, DEFAULT_PROJECT, &prompter).unwrap());
+}
+
+#[test]
+fn project_delete_confirmed() {
+    let mut store = Store::new();
+    let _ = store.create("work");
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let command = ProjectCommand::Delete {
+        name: String::from("work"),
+    };
+    let result = run_project(command, &mut store, DEFAULT_PROJECT, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert!(store.project("work").is_err());
+}
+
+#[test]
+fn project_delete_not_confirmed() {
+    let mut store = Store::new();
+    let _ = store.create("work");
+
+    let prompter = FakePrompter::select_first_and_not_confirm();
+    let command = ProjectCommand::Delete {
+        name: String::from("work"),
+    };
+    let result = run_project(command, &mut store, DEFAULT_PROJECT, &prompter).unwrap();
+
+    assert_no_change(&result);
+    assert!(store.project("work").is_ok());
+}
+
+#[test]
+fn project_move() {
+    let mut store = Store::new();
+    let _ = store.create("work");
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add("Test".into());
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let command = ProjectCommand::Move {
+        to: String::from("work"),
+    };
+    let result = run_project(command, &mut store, DEFAULT_PROJECT, &prompter).unwrap();
+
+    assert_changed(&result);
+    assert!(store.project(DEFAULT_PROJECT).unwrap().list().is_empty());
+    assert_eq!(store.project("work").unwrap().list().len(), 1);
+}
+
+#[test]
+fn project_move_not_selected() {
+    let mut store = Store::new();
+    let _ = store.create("work");
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add("Test".into());
+
+    let prompter = FakePrompter::nothing_selected();
+    let command = ProjectCommand::Move {
+        to: String::from("work"),
+    };
+    let result = run_project(command, &mut store, DEFAULT_PROJECT, &prompter).unwrap();
+
+    assert_no_change(&result);
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().list().len(), 1);
+}
+
+#[test]
+fn doctor_restores_backup_confirmed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    let mut store = Store::new();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add("Test".into());
+    save_todos(&path, &store).unwrap();
+    std::fs::copy(&path, backup_path(&path)).unwrap();
+    std::fs::write(&path, "{\"projects\": ").unwrap();
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = doctor(&path, &prompter).unwrap();
+
+    assert_changed(&result);
+    let store = load_todos(&path).unwrap();
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().list().len(), 1);
+    assert!(dir.path().join("todo.json.corrupt").exists());
+}
+
+#[test]
+fn doctor_replays_events_logged_after_the_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    let storage = storage::open(Backend::Json, path.clone()).unwrap();
+    let mut store = Store::new();
+    for x in 0..SNAPSHOT_INTERVAL + 5 {
+        store
+            .project_mut(DEFAULT_PROJECT)
+            .unwrap()
+            .add(x.to_string());
+        storage.save(&store).unwrap();
+    }
+    // The backup is the first snapshot, the rest are in both logs
+    std::fs::write(&path, "{\"projects\": ").unwrap();
+
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = doctor(&path, &prompter).unwrap();
+
+    assert_changed(&result);
+    let loaded = storage.load().unwrap();
+    assert_eq!(
+        loaded.project(DEFAULT_PROJECT).unwrap(),
+        store.project(DEFAULT_PROJECT).unwrap()
+    );
+}
+
+#[test]
+fn doctor_not_confirmed_keeps_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    save_todos(&path, &Store::new()).unwrap();
+    std::fs::copy(&path, backup_path(&path)).unwrap();
+    std::fs::write(&path, "{").unwrap();
+
+    let prompter = FakePrompter::select_first_and_not_confirm();
+    let result = doctor(&path, &prompter).unwrap();
+
+    assert_no_change(&result);
+    assert!(load_todos(&path).is_err());
+}
+
+#[test]
+fn doctor_repairs_event_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    let storage = storage::open(Backend::Json, path.clone()).unwrap();
+    let mut store = Store::new();
+    for title in ["One", "Two", "Three"] {
+        store
+            .project_mut(DEFAULT_PROJECT)
+            .unwrap()
+            .add(title.into());
+        storage.save(&store).unwrap();
+    }
+    // The first event is in the snapshot, the log holds the other two
+    let log = std::fs::read_to_string(events_path(&path)).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    std::fs::write(events_path(&path), format!("{{oops\n{}\n", lines[1])).unwrap();
+    assert!(storage.load().is_err());
+
+    // The first option keeps every readable event
+    let prompter = FakePrompter::select_first_and_confirm();
+    let result = doctor(&path, &prompter).unwrap();
+
+    assert_changed(&result);
+    let store = storage.load().unwrap();
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().list().len(), 2);
+    assert!(dir.path().join("todo.json.events.jsonl.corrupt").exists());
+    assert_no_change(&doctor(&path, &prompter).unwrap());
+}
+
+#[test]
+fn import_json_into_empty_storage() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("old.json");
+    let mut store = Store::new();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add("Test".into());
+    save_todos(&source, &store).unwrap();
+    let target = storage::open(Backend::Json, dir.path().join("new.json")).unwrap();
+
+    let prompter = FakePrompter::nothing_selected();
+    let result = import_json(&source, target.as_ref(), &prompter).unwrap();
+
+    assert_changed(&result);
+    let store = target.load().unwrap();
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().list().len(), 1);
+}
+
+#[test]
+fn import_json_includes_logged_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = storage::open(Backend::Json, dir.path().join("old.json")).unwrap();
+    let mut store = Store::new();
+    for title in ["One", "Two", "Three"] {
+        store
+            .project_mut(DEFAULT_PROJECT)
+            .unwrap()
+            .add(title.into());
+        source.save(&store).unwrap();
+    }
+    let target = storage::open(Backend::Json, dir.path().join("new.json")).unwrap();
+
+    let prompter = FakePrompter::nothing_selected();
+    import_json(source.path(), target.as_ref(), &prompter).unwrap();
+
+    let store = target.load().unwrap();
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().list().len(), 3);
+}
+
+#[test]
+fn import_json_over_existing_todos_not_confirmed() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("old.json");
+    save_todos(&source, &Store::new()).unwrap();
+    let target = storage::open(Backend::Json, dir.path().join("new.json")).unwrap();
+    let mut store = Store::new();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add("Keep".into());
+    target.save(&store).unwrap();
+
+    let prompter = FakePrompter::select_first_and_not_confirm();
+    let result = import_json(&source, target.as_ref(), &prompter).unwrap();
+
+    assert_no_change(&result);
+    let store = target.load().unwrap();
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().list().len(), 1);
+}
+
+#[test]
+fn migrate_dry_run_leaves_file_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    let legacy = r#"{"items": [{"id": 1, "title": "Test", "done": false}], "next_id": 2}"#;
+    std::fs::write(&path, legacy).unwrap();
+
+    let result = migrate(&path, true).unwrap();
+
+    assert_no_change(&result);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), legacy);
+}
+
+#[test]
+fn migrate_upgrades_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    let legacy = r#"{"items": [{"id": 1, "title": "Test", "done": false}], "next_id": 2}"#;
+    std::fs::write(&path, legacy).unwrap();
+
+    let result = migrate(&path, false).unwrap();
+
+    assert_changed(&result);
+    assert!(
+        std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("schema_version")
+    );
+    assert_eq!(migrate(&path, false).unwrap(), RunResult::NoChange);
+}
+
+#[test]
+fn undo_then_redo_delete() {
+    let mut store = Store::new();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add("Test".into());
+    let mut archive = Store::new();
+    let mut history = History::default();
+    let before = store.clone();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .remove(1)
+        .unwrap();
+    history.record(String::from("delete [1] Test"), &before, &store);
+
+    assert_changed(&undo(&mut store, &mut archive, &mut history));
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().list().len(), 1);
+
+    assert_changed(&redo(&mut store, &mut archive, &mut history));
+    assert!(store.project(DEFAULT_PROJECT).unwrap().list().is_empty());
+    assert_no_change(&redo(&mut store, &mut archive, &mut history));
+}
+
+#[test]
+fn archive_unknown_project() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+
+    assert_eq!(
+        archive(storage.as_ref(), "work", None),
+        Err(String::from("Project 'work' is not found"))
+    );
+}
+
+#[test]
+fn archive_before_date() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+    let mut store = Store::new();
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.add("Test".into());
+    todos.mark(1, true).unwrap();
+    storage.save(&store).unwrap();
+
+    let result = archive(
+        storage.as_ref(),
+        DEFAULT_PROJECT,
+        NaiveDate::from_ymd_opt(2000, 1, 1),
+    );
+    assert_no_change(&result.unwrap());
+
+    assert_changed(&archive(storage.as_ref(), DEFAULT_PROJECT, None).unwrap());
+    assert_eq!(
+        storage
+            .load_archive()
+            .unwrap()
+            .project(DEFAULT_PROJECT)
+            .unwrap()
+            .items
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn mark_done_and_undo_done_by_ids() {
+    let mut todos = TodoList::new();
+    todos.add("First".into());
+    todos.add("Second".into());
+    let prompter = FakePrompter::nothing_selected();
+
+    let result = run(
+        Command::MarkDone {
+            ids: vec!["1".parse().unwrap(), "2".parse().unwrap()],
+        },
+        &mut todos,
+        &prompter,
+    )
+    .unwrap();
+    assert_changed(&result);
+    assert_eq!(todos.done().count(), 2);
+
+    let result = run(
+        Command::MarkDone {
+            ids: vec!["1".parse().unwrap()],
+        },
+        &mut todos,
+        &prompter,
+    )
+    .unwrap();
+    assert_no_change(&result);
+
+    let result = run(
+        Command::UndoDone {
+            ids: vec!["2".parse().unwrap()],
+        },
+        &mut todos,
+        &prompter,
+    )
+    .unwrap();
+    assert_changed(&result);
+    assert_eq!(todos.done().count(), 1);
+}
+
+#[test]
+fn update_by_id_with_title() {
+    let mut todos = TodoList::new();
+    todos.add("First".into());
+    todos.add("Second".into());
+
+    let result = run(
+        Command::Update {
+            id: Some(2),
+            title: Some(String::from("Renamed")),
+        },
+        &mut todos,
+        &FakePrompter::nothing_selected(),
+    )
+    .unwrap();
+
+    assert_changed(&result);
+    assert_eq!(todos.items[1].title, "Renamed");
+}
+
+#[test]
+fn id_lists_and_ranges() {
+    assert_eq!("3, 5,7-10".parse(), Ok(IdList(vec![3, 5, 7, 8, 9, 10])));
+    assert_eq!(
+        "10-7".parse::<IdList>(),
+        Err(String::from("Invalid id range '10-7'"))
+    );
+    assert_eq!(
+        "3,x".parse::<IdList>(),
+        Err(String::from("Invalid todo id 'x'"))
+    );
+    assert_eq!(
+        "1-18446744073709551615".parse::<IdList>(),
+        Err(String::from(
+            "Id range '1-18446744073709551615' is longer than 10000 ids"
+        ))
+    );
+    assert_eq!(
+        format!("1-{}", MAX_ID_RANGE)
+            .parse::<IdList>()
+            .unwrap()
+            .0
+            .len() as u64,
+        MAX_ID_RANGE
+    );
+}
+
 fn assert_changed(result: &RunResult) {
     assert_eq!(*result, RunResult::Changed);
 }
@@ -0,0 +1,88 @@
+// Fixtures shared by the integration tests; each test crate only uses some
+// of them
+#![allow(dead_code)]
+
+use chrono::NaiveDate;
+use todo::shared::store::{DEFAULT_PROJECT, Store};
+use todo::shared::todo::{Priority, TodoList};
+
+pub fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+/// Two todos in the default project and one in `work`
+pub fn projects() -> Store {
+    let mut store = Store::new();
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.add(String::from("Buy milk"));
+    let id = todos.add(String::from("Walk dog")).id;
+    todos.set_priority(id, Priority::High).unwrap();
+    todos.add_tag(id, "home").unwrap();
+    store.create("work").unwrap();
+    store
+        .project_mut("work")
+        .unwrap()
+        .add(String::from("Write report"));
+    store
+}
+
+/// Adds a todo to the default project, returning its id
+pub fn add(store: &mut Store, title: &str) -> u64 {
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add(title.to_string())
+        .id
+}
+
+/// Titles of the todos in the default project
+pub fn titles(store: &Store) -> Vec<String> {
+    let todos = store.project(DEFAULT_PROJECT).unwrap();
+    todos.items.iter().map(|x| x.title.clone()).collect()
+}
+
+/// Todos whose titles need quoting or escaping in most output formats
+pub fn awkward() -> TodoList {
+    let mut todos = TodoList::new();
+    todos.add(String::from("Buy milk, eggs"));
+    todos.add(String::from("Say \"hi\" | wave"));
+    todos.add_tag(2, "home").unwrap();
+    todos.add_tag(2, "social").unwrap();
+    todos.mark(1, true).unwrap();
+    todos
+}
+
+/// Todos that mention the plumber in a title or in notes, and one tagged
+pub fn searchable() -> TodoList {
+    let mut todos = TodoList::new();
+    todos.add(String::from("Call the plumber"));
+    todos.add(String::from("Replace kitchen tap"));
+    todos.set_notes(2, "Ask the PLUMBER which tap").unwrap();
+    todos.add(String::from("Book flights"));
+    todos.add_tag(3, "travel").unwrap();
+    todos
+}
+
+/// The day `filterable()` is filtered on, a Wednesday
+pub fn today() -> NaiveDate {
+    date(2030, 1, 2)
+}
+
+/// Todos with tags, due dates, a priority and notes to filter on
+pub fn filterable() -> TodoList {
+    let mut todos = TodoList::new();
+    todos.add(String::from("Fix login bug"));
+    todos.add_tag(1, "backend").unwrap();
+    todos.set_due(1, Some(today())).unwrap();
+    todos.add(String::from("Write docs"));
+    todos.add_tag(2, "backend").unwrap();
+    todos.add_tag(2, "someday").unwrap();
+    todos.add(String::from("Plan offsite"));
+    todos
+        .set_due(3, NaiveDate::from_ymd_opt(2030, 1, 10))
+        .unwrap();
+    todos.set_priority(3, Priority::High).unwrap();
+    todos.set_notes(3, "Book the venue").unwrap();
+    todos.mark(2, true).unwrap();
+    todos
+}
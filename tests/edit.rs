@@ -0,0 +1,77 @@
+use chrono::NaiveDate;
+use std::collections::BTreeSet;
+use todo::shared::edit::TodoEdit;
+use todo::shared::todo::{Priority, TodoList};
+
+#[test]
+fn render_and_parse_round_trip() {
+    let edit = TodoEdit {
+        title: String::from("Write report"),
+        due: NaiveDate::from_ymd_opt(2030, 1, 31),
+        priority: Priority::Medium,
+        tags: BTreeSet::from([String::from("backend"), String::from("urgent")]),
+        repeat: "every 2 weeks".parse().ok(),
+        notes: String::from("First line\n\nThird line"),
+    };
+
+    assert_eq!(TodoEdit::parse(&edit.render()), Ok(edit));
+}
+
+#[test]
+fn parse_empty_fields() {
+    let edit = TodoEdit::parse("title: Task\ndue:\npriority:\ntags:\n---\n").unwrap();
+
+    assert_eq!(edit.title, "Task");
+    assert_eq!(edit.due, None);
+    assert_eq!(edit.priority, Priority::None);
+    assert!(edit.tags.is_empty());
+    assert_eq!(edit.repeat, None);
+    assert!(edit.notes.is_empty());
+}
+
+#[test]
+fn parse_missing_title() {
+    assert_eq!(
+        TodoEdit::parse("due: 2030-01-31\n---\n"),
+        Err(String::from("Title must not be empty"))
+    );
+}
+
+#[test]
+fn parse_unknown_field() {
+    assert_eq!(
+        TodoEdit::parse("title: Task\ncolour: red\n"),
+        Err(String::from("Line 2: unknown field 'colour'"))
+    );
+}
+
+#[test]
+fn parse_invalid_priority() {
+    assert_eq!(
+        TodoEdit::parse("title: Task\npriority: urgent\n"),
+        Err(String::from("Line 2: invalid priority 'urgent'"))
+    );
+}
+
+#[test]
+fn parse_invalid_repeat() {
+    assert_eq!(
+        TodoEdit::parse("title: Task\nrepeat: sometimes\n"),
+        Err(String::from("Line 2: Invalid recurrence 'sometimes'"))
+    );
+}
+
+#[test]
+fn apply_edit() {
+    let mut todos = TodoList::new();
+    todos.add(String::from("Task"));
+    let _ = todos.add_tag(1, "old");
+
+    let edit = TodoEdit::parse("title: Task\ntags: +new\n---\nSome notes").unwrap();
+    let _ = edit.apply(&mut todos, 1);
+
+    let todo = todos.get(1).unwrap();
+    assert!(todo.has_tag("new"));
+    assert!(!todo.has_tag("old"));
+    assert_eq!(todo.notes, "Some notes");
+}
@@ -0,0 +1,301 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use todo::shared::events::{Change, SNAPSHOT_INTERVAL, diff};
+use todo::shared::file::load_todos;
+use todo::shared::migrate::SCHEMA_VERSION;
+use todo::shared::storage::{self, Backend};
+use todo::shared::store::{DEFAULT_PROJECT, Store};
+use todo::shared::todo::{Priority, Status, TodoList};
+
+mod common;
+use common::add;
+
+#[test]
+fn diff_finds_added_updated_and_removed_todos() {
+    let mut before = Store::new();
+    add(&mut before, "Buy milk");
+    add(&mut before, "Walk dog");
+    let mut after = before.clone();
+    let todos = after.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.remove(1).unwrap();
+    todos.update_title(2, "Walk the dog").unwrap();
+    todos.add(String::from("Feed cat"));
+
+    let changes: Vec<String> = diff(&before, &after)
+        .into_iter()
+        .map(|(_, x)| match x {
+            Change::Add { todo } => format!("add {}", todo.id),
+            Change::Rename { todo, .. } => format!("rename {}", todo.id),
+            Change::Trash { entry } => format!("trash {}", entry.todo.id),
+            other => format!("{:?}", other),
+        })
+        .collect();
+
+    assert_eq!(changes, vec!["trash 1", "rename 2", "add 3"]);
+}
+
+#[test]
+fn diff_names_edits_after_the_fields_changed() {
+    let mut before = Store::new();
+    add(&mut before, "Buy milk");
+    add(&mut before, "Walk dog");
+    add(&mut before, "Feed cat");
+    before
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .mark(3, true)
+        .unwrap();
+    let mut after = before.clone();
+    let todos = after.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.mark(1, true).unwrap();
+    todos.set_priority(2, Priority::High).unwrap();
+    todos.set_notes(2, "Around the park").unwrap();
+    todos.set_status(3, Status::InProgress).unwrap();
+
+    let described: Vec<String> = diff(&before, &after)
+        .iter()
+        .map(|(project, x)| x.describe(project))
+        .collect();
+
+    assert_eq!(
+        described,
+        vec![
+            "complete [1] Buy milk",
+            "update [2] Walk dog (priority, notes)",
+            "reopen [3] Feed cat",
+        ]
+    );
+}
+
+#[test]
+fn diff_detects_reset() {
+    let mut before = Store::new();
+    add(&mut before, "Buy milk");
+    let mut after = before.clone();
+    *after.project_mut(DEFAULT_PROJECT).unwrap() = TodoList::new();
+
+    let changes = diff(&before, &after);
+
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].1, Change::Reset);
+}
+
+#[test]
+fn state_is_snapshot_plus_replayed_events() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    let storage = storage::open(Backend::Json, path.clone()).unwrap();
+
+    let mut store = storage.load().unwrap();
+    for x in 0..SNAPSHOT_INTERVAL + 5 {
+        add(&mut store, &format!("Todo {}", x));
+        storage.save(&store).unwrap();
+    }
+    store.create("work").unwrap();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .remove(3)
+        .unwrap();
+    storage.save(&store).unwrap();
+
+    let snapshot = load_todos(&path).unwrap();
+    assert_eq!(snapshot.seq(), SNAPSHOT_INTERVAL);
+    assert!(storage.events(snapshot.seq()).unwrap().len() > 1);
+
+    let loaded = storage.load().unwrap();
+    assert_eq!(loaded.seq(), SNAPSHOT_INTERVAL + 7);
+    let mut expected = store.clone();
+    expected.set_seq(loaded.seq());
+    assert_eq!(loaded, expected);
+}
+
+#[test]
+fn covered_events_are_rotated_out_of_the_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    let storage = storage::open(Backend::Json, path.clone()).unwrap();
+
+    let mut store = storage.load().unwrap();
+    for x in 0..SNAPSHOT_INTERVAL + 5 {
+        add(&mut store, &format!("Todo {}", x));
+        storage.save(&store).unwrap();
+    }
+
+    let recent = std::fs::read_to_string(dir.path().join("todo.json.events.jsonl")).unwrap();
+    assert_eq!(recent.lines().count(), 5);
+    let events = storage.events(0).unwrap();
+    assert_eq!(events.len() as u64, SNAPSHOT_INTERVAL + 5);
+    assert!(events.windows(2).all(|x| x[0].seq + 1 == x[1].seq));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_keeps_events_in_the_database() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.db");
+    let storage = storage::open(Backend::Sqlite, path.clone()).unwrap();
+
+    let mut store = storage.load().unwrap();
+    add(&mut store, "Buy milk");
+    storage.save(&store).unwrap();
+    add(&mut store, "Walk dog");
+    storage.save(&store).unwrap();
+
+    assert!(!dir.path().join("todo.db.events.jsonl").exists());
+    let snapshot = storage.load_snapshot().unwrap();
+    assert_eq!(snapshot.seq(), 2);
+    assert_eq!(snapshot.project(DEFAULT_PROJECT).unwrap().items.len(), 2);
+    assert_eq!(storage.events(1).unwrap()[0].describe(), "add [2] Walk dog");
+}
+
+#[test]
+fn events_since() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+    let mut store = storage.load().unwrap();
+    add(&mut store, "Buy milk");
+    storage.save(&store).unwrap();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .update_title(1, "Buy oat milk")
+        .unwrap();
+    storage.save(&store).unwrap();
+
+    let events = storage.events(1).unwrap();
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].seq, 2);
+    assert_eq!(events[0].describe(), "rename [1] Buy milk to Buy oat milk");
+}
+
+#[test]
+fn torn_last_line_is_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+    let mut store = storage.load().unwrap();
+    add(&mut store, "Buy milk");
+    storage.save(&store).unwrap();
+
+    // The first save rotated the log, so this starts a new one
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.path().join("todo.json.events.jsonl"))
+        .unwrap();
+    log.write_all(b"{\"seq\": 2, \"at\"").unwrap();
+
+    assert_eq!(storage.events(0).unwrap().len(), 1);
+    assert_eq!(
+        storage
+            .load()
+            .unwrap()
+            .project(DEFAULT_PROJECT)
+            .unwrap()
+            .items
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn restore_and_purge_are_replayed() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+    let mut store = storage.load().unwrap();
+    add(&mut store, "Buy milk");
+    add(&mut store, "Walk dog");
+    storage.save(&store).unwrap();
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.remove(1).unwrap();
+    todos.remove(2).unwrap();
+    storage.save(&store).unwrap();
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.restore(1).unwrap();
+    todos.trash.clear();
+    storage.save(&store).unwrap();
+
+    let described: Vec<String> = storage
+        .events(4)
+        .unwrap()
+        .iter()
+        .map(|x| x.describe())
+        .collect();
+    assert_eq!(
+        described,
+        vec!["restore [1] Buy milk", "purge [2] Walk dog"]
+    );
+
+    let loaded = storage.load().unwrap();
+    let mut expected = store.clone();
+    expected.set_seq(loaded.seq());
+    assert_eq!(loaded, expected);
+}
+
+#[test]
+fn replay_keeps_ids_handed_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+    let mut store = storage.load().unwrap();
+    add(&mut store, "Buy milk");
+    storage.save(&store).unwrap();
+    // Only the trash entry is logged for a todo deleted as it is added
+    let id = add(&mut store, "Walk dog");
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .remove(id)
+        .unwrap();
+    storage.save(&store).unwrap();
+
+    let loaded = storage.load().unwrap();
+    assert_eq!(loaded.project(DEFAULT_PROJECT).unwrap().next_id(), 3);
+}
+
+#[test]
+fn events_logged_without_a_schema_version_are_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+    storage.save(&Store::new()).unwrap();
+    std::fs::write(
+        dir.path().join("todo.json.events.jsonl"),
+        concat!(
+            r#"{"seq":1,"at":"2025-01-01T00:00:00Z","actor":"alice via todo-cli","#,
+            r#""project":"default","type":"add","todo":{"id":1,"title":"Buy milk","done":true}}"#,
+            "\n"
+        ),
+    )
+    .unwrap();
+
+    let events = storage.events(0).unwrap();
+    assert_eq!(events[0].schema_version, SCHEMA_VERSION);
+    let loaded = storage.load().unwrap();
+    assert!(
+        loaded
+            .project(DEFAULT_PROJECT)
+            .unwrap()
+            .get(1)
+            .unwrap()
+            .is_done()
+    );
+}
+
+#[test]
+fn events_logged_with_a_newer_schema_are_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+    storage.save(&Store::new()).unwrap();
+    std::fs::write(
+        dir.path().join("todo.json.events.jsonl"),
+        concat!(
+            r#"{"seq":1,"at":"2025-01-01T00:00:00Z","actor":"alice via todo-cli","#,
+            r#""project":"default","schema_version":99,"type":"reset"}"#,
+            "\n"
+        ),
+    )
+    .unwrap();
+
+    let e = storage.load().unwrap_err().to_string();
+    assert!(e.contains("newer than the supported version"), "{}", e);
+}
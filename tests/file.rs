@@ -0,0 +1,189 @@
+use std::path::PathBuf;
+use todo::shared::config::Config;
+use todo::shared::file::{
+    FileError, backup_path, load_todos, lock, salvage, save_todos, stamp, upgrade_backup_path,
+};
+use todo::shared::migrate::SCHEMA_VERSION;
+use todo::shared::store::{DEFAULT_PROJECT, Store};
+
+#[test]
+fn missing_file_loads_empty_store() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let store = load_todos(&dir.path().join("todo.json")).unwrap();
+
+    assert_eq!(store.names().collect::<Vec<_>>(), vec![DEFAULT_PROJECT]);
+}
+
+#[test]
+fn save_creates_parent_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nested/dir/todo.json");
+    let mut store = Store::new();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add(String::from("Buy milk"));
+
+    save_todos(&path, &store).unwrap();
+
+    let loaded = load_todos(&path).unwrap();
+    assert_eq!(
+        loaded.project(DEFAULT_PROJECT).unwrap().items[0].title,
+        "Buy milk"
+    );
+}
+
+#[test]
+fn flag_wins_over_config_file() {
+    let config = Config {
+        file: Some(PathBuf::from("/from/config.json")),
+        ..Config::default()
+    };
+
+    let path = config.store_path(Some(PathBuf::from("/from/flag.json")));
+
+    assert_eq!(path, Ok(PathBuf::from("/from/flag.json")));
+}
+
+#[test]
+fn save_keeps_previous_version_as_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    let mut store = Store::new();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add(String::from("Buy milk"));
+    save_todos(&path, &store).unwrap();
+
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add(String::from("Walk dog"));
+    save_todos(&path, &store).unwrap();
+
+    let backup = load_todos(&backup_path(&path)).unwrap();
+    assert_eq!(backup.project(DEFAULT_PROJECT).unwrap().items.len(), 1);
+    assert_eq!(
+        load_todos(&path)
+            .unwrap()
+            .project(DEFAULT_PROJECT)
+            .unwrap()
+            .items
+            .len(),
+        2
+    );
+    assert!(!dir.path().join("todo.json.tmp").exists());
+}
+
+#[test]
+fn save_reports_error_instead_of_panicking() {
+    let dir = tempfile::tempdir().unwrap();
+    let blocker = dir.path().join("not-a-dir");
+    std::fs::write(&blocker, "").unwrap();
+
+    let result = save_todos(&blocker.join("todo.json"), &Store::new());
+
+    assert!(result.unwrap_err().to_string().contains("not-a-dir"));
+}
+
+#[test]
+fn corrupt_file_is_an_error_with_position() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    std::fs::write(&path, "{\n  \"projects\": {\n    \"default\": [x]\n").unwrap();
+
+    let error = load_todos(&path).unwrap_err();
+
+    match &error {
+        FileError::Corrupt { line, column, .. } => assert_eq!((*line, *column), (3, 17)),
+        other => panic!("unexpected error {other}"),
+    }
+    assert!(
+        error
+            .to_string()
+            .contains("todo.json is corrupt at line 3, column 17")
+    );
+}
+
+#[test]
+fn legacy_file_loads_into_default_project() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    std::fs::write(
+        &path,
+        r#"{"items": [{"id": 1, "title": "Buy milk", "done": true}], "next_id": 2}"#,
+    )
+    .unwrap();
+
+    let store = load_todos(&path).unwrap();
+
+    let todos = store.project(DEFAULT_PROJECT).unwrap();
+    assert!(todos.items[0].is_done());
+    assert!(upgrade_backup_path(&path, 0).exists());
+}
+
+#[test]
+fn saved_file_records_schema_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+
+    save_todos(&path, &Store::new()).unwrap();
+
+    let value: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(value["schema_version"], SCHEMA_VERSION);
+}
+
+#[test]
+fn salvage_drops_unreadable_todos() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    std::fs::write(
+        &path,
+        r#"{"projects": {"default": {"items": [
+            {"id": 1, "title": "Buy milk"},
+            {"id": 2, "title": 42}
+        ], "next_id": 3}}}"#,
+    )
+    .unwrap();
+
+    assert!(load_todos(&path).is_err());
+    let (store, dropped) = salvage(&path).unwrap();
+
+    assert_eq!(dropped, 1);
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().items.len(), 1);
+}
+
+#[test]
+fn lock_excludes_other_holders_until_dropped() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+
+    let held = lock(&path).unwrap();
+    let other = std::fs::File::open(dir.path().join("todo.json.lock")).unwrap();
+    assert!(other.try_lock().is_err());
+
+    drop(held);
+    assert!(other.try_lock().is_ok());
+}
+
+#[test]
+fn stamp_changes_when_file_is_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("todo.json");
+    assert_eq!(stamp(&path), None);
+
+    save_todos(&path, &Store::new()).unwrap();
+    let first = stamp(&path);
+    let mut store = Store::new();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add(String::from("Buy milk"));
+    save_todos(&path, &store).unwrap();
+
+    assert!(first.is_some());
+    assert_ne!(stamp(&path), first);
+}
@@ -0,0 +1,85 @@
+use todo::shared::filter::{Filter, FilterError, MAX_DEPTH};
+
+mod common;
+use common::{filterable, today};
+
+fn ids(filter: &str) -> Vec<u64> {
+    let todos = filterable();
+    let filter = Filter::parse(filter, today()).unwrap();
+    todos
+        .items
+        .iter()
+        .filter(|x| filter.matches(x, today()))
+        .map(|x| x.id)
+        .collect()
+}
+
+#[test]
+fn terms_are_combined_with_and() {
+    assert_eq!(ids("tag:backend"), vec![1, 2]);
+    assert_eq!(ids("status:open tag:backend"), vec![1]);
+    assert_eq!(ids("tag:backend -tag:someday"), vec![1]);
+}
+
+#[test]
+fn or_and_parentheses() {
+    assert_eq!(ids("priority:high or is:done"), vec![2, 3]);
+    assert_eq!(ids("-(tag:someday or due:none)"), vec![1, 3]);
+    assert_eq!(ids("is:open and (venue or bug)"), vec![1, 3]);
+}
+
+#[test]
+fn dates_and_comparisons() {
+    assert_eq!(ids("due<friday"), vec![1]);
+    assert_eq!(ids("due<=today"), vec![1]);
+    assert_eq!(ids("due>2030-01-05"), vec![3]);
+    assert_eq!(ids("priority>low"), vec![3]);
+    assert_eq!(ids("id>=2 \"the venue\""), vec![3]);
+}
+
+#[test]
+fn errors_point_at_the_problem() {
+    let error = |input: &str| Filter::parse(input, today()).unwrap_err();
+
+    assert_eq!(
+        error("status:open (tag:x"),
+        FilterError {
+            column: 19,
+            message: String::from("Expected ')'"),
+        }
+    );
+    assert_eq!(
+        error("tag:x colour:red").to_string(),
+        "Unknown field 'colour' at column 7"
+    );
+    assert_eq!(
+        error("due<someday").to_string(),
+        "Invalid date 'someday' at column 5"
+    );
+    assert_eq!(error("tag:x )").to_string(), "Unexpected ')' at column 7");
+    assert_eq!(
+        error("title:\"open").to_string(),
+        "Unclosed quote at column 7"
+    );
+    assert_eq!(
+        error("status<open").to_string(),
+        "'status' cannot be compared at column 1"
+    );
+}
+
+#[test]
+fn nesting_is_limited() {
+    let nested = format!("{}x{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+    assert!(Filter::parse(&nested, today()).is_ok());
+
+    let negated = format!("{}x", "-".repeat(2000));
+    assert_eq!(
+        Filter::parse(&negated, today()).unwrap_err(),
+        FilterError {
+            column: MAX_DEPTH + 1,
+            message: String::from("The filter is nested too deeply"),
+        }
+    );
+    let opened = "(".repeat(100_000);
+    assert!(Filter::parse(&opened, today()).is_err());
+}
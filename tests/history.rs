@@ -0,0 +1,120 @@
+use todo::shared::history::{HISTORY_LIMIT, History, describe};
+use todo::shared::store::{DEFAULT_PROJECT, Store};
+use todo::shared::todo::TodoList;
+
+mod common;
+use common::{add, titles};
+
+#[test]
+fn undo_and_redo() {
+    let mut store = Store::new();
+    let mut archive = Store::new();
+    let mut history = History::default();
+    let before = store.clone();
+    add(&mut store, "Buy milk");
+    history.record(describe(&before, &store), &before, &store);
+
+    assert_eq!(
+        history.undo(&mut store, &mut archive),
+        Some(String::from("add [1] Buy milk"))
+    );
+    assert!(titles(&store).is_empty());
+    assert_eq!(history.undo(&mut store, &mut archive), None);
+
+    assert_eq!(
+        history.redo(&mut store, &mut archive),
+        Some(String::from("add [1] Buy milk"))
+    );
+    assert_eq!(titles(&store), vec!["Buy milk"]);
+    assert_eq!(history.redo(&mut store, &mut archive), None);
+}
+
+#[test]
+fn new_change_clears_redo() {
+    let mut store = Store::new();
+    let mut archive = Store::new();
+    let mut history = History::default();
+    let before = store.clone();
+    add(&mut store, "Buy milk");
+    history.record(String::from("first"), &before, &store);
+    history.undo(&mut store, &mut archive);
+
+    history.record(String::from("second"), &before, &store);
+
+    assert!(!history.can_redo());
+}
+
+#[test]
+fn history_is_bounded() {
+    let mut store = Store::new();
+    let mut archive = Store::new();
+    let mut history = History::default();
+    for x in 0..HISTORY_LIMIT + 5 {
+        let before = store.clone();
+        add(&mut store, &x.to_string());
+        history.record(x.to_string(), &before, &store);
+    }
+
+    let mut undone = 0;
+    while history.undo(&mut store, &mut archive).is_some() {
+        undone += 1;
+    }
+
+    assert_eq!(undone, HISTORY_LIMIT);
+}
+
+#[test]
+fn undo_leaves_changes_made_since_alone() {
+    let mut store = Store::new();
+    let mut archive = Store::new();
+    let mut history = History::default();
+    add(&mut store, "Buy milk");
+    add(&mut store, "Walk dog");
+    let before = store.clone();
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.update_title(1, "Buy oat milk").unwrap();
+    history.record(describe(&before, &store), &before, &store);
+    // Not recorded, like trash purged on load
+    let todos = store.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.update_title(2, "Walk the dog").unwrap();
+
+    assert_eq!(
+        history.undo(&mut store, &mut archive),
+        Some(String::from("rename [1] Buy milk to Buy oat milk"))
+    );
+    assert_eq!(titles(&store), vec!["Buy milk", "Walk the dog"]);
+}
+
+#[test]
+fn undo_restores_ids() {
+    let mut store = Store::new();
+    let mut archive = Store::new();
+    let mut history = History::default();
+    add(&mut store, "Buy milk");
+    let before = store.clone();
+    *store.project_mut(DEFAULT_PROJECT).unwrap() = TodoList::new();
+    history.record(describe(&before, &store), &before, &store);
+
+    history.undo(&mut store, &mut archive);
+    assert_eq!(store, before);
+
+    history.redo(&mut store, &mut archive);
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().next_id(), 1);
+}
+
+#[test]
+fn describe_names_changes() {
+    let mut before = Store::new();
+    let id = add(&mut before, "Buy milk");
+    add(&mut before, "Walk dog");
+    let mut after = before.clone();
+    let todos = after.project_mut(DEFAULT_PROJECT).unwrap();
+    todos.remove(id).unwrap();
+    todos.update_title(2, "Walk the dog").unwrap();
+    after.create("work").unwrap();
+
+    assert_eq!(
+        describe(&before, &after),
+        "delete [1] Buy milk, rename [2] Walk dog to Walk the dog, create project work"
+    );
+}
@@ -0,0 +1,44 @@
+use serde_json::json;
+use todo::shared::migrate::{SCHEMA_VERSION, pending, upgrade, version_of};
+
+#[test]
+fn detects_versions_before_schema_version_existed() {
+    assert_eq!(version_of(&json!({"items": [], "next_id": 1})), 0);
+    assert_eq!(version_of(&json!({"projects": {}})), 1);
+    assert_eq!(version_of(&json!({"schema_version": 2, "projects": {}})), 2);
+}
+
+#[test]
+fn upgrades_legacy_list_step_by_step() {
+    let mut value = json!({
+        "items": [
+            {"id": 1, "title": "Buy milk", "done": true},
+            {"id": 2, "title": "Walk dog", "done": false, "status": "in-progress"}
+        ],
+        "next_id": 3
+    });
+
+    let applied = upgrade(&mut value).unwrap();
+
+    let steps: Vec<(u32, usize)> = applied.iter().map(|(x, n)| (x.from, *n)).collect();
+    assert_eq!(steps, vec![(0, 1), (1, 1)]);
+    assert_eq!(value["schema_version"], json!(SCHEMA_VERSION));
+    let items = &value["projects"]["default"]["items"];
+    assert_eq!(items[0]["status"], json!("done"));
+    assert_eq!(items[1]["status"], json!("in-progress"));
+}
+
+#[test]
+fn current_version_has_nothing_pending() {
+    let mut value = json!({"schema_version": SCHEMA_VERSION, "projects": {}});
+
+    assert!(pending(SCHEMA_VERSION).is_empty());
+    assert!(upgrade(&mut value).unwrap().is_empty());
+}
+
+#[test]
+fn newer_version_is_refused() {
+    let mut value = json!({"schema_version": SCHEMA_VERSION + 1, "projects": {}});
+
+    assert!(upgrade(&mut value).is_err());
+}
@@ -0,0 +1,69 @@
+use todo::shared::output::{Format, render};
+use todo::shared::todo::Todo;
+
+mod common;
+use common::awkward;
+
+#[test]
+fn json_matches_api_response() {
+    let todos = awkward();
+    let items: Vec<&Todo> = todos.items.iter().collect();
+
+    let value: serde_json::Value = serde_json::from_str(&render(Format::Json, &items)).unwrap();
+
+    assert_eq!(value[0]["id"], 1);
+    assert_eq!(value[0]["done"], true);
+    assert_eq!(value[1]["status"], "open");
+    assert_eq!(value[1]["tags"], serde_json::json!(["home", "social"]));
+    assert!(value[1]["parent_id"].is_null());
+}
+
+#[test]
+fn jsonl_writes_one_todo_per_line() {
+    let todos = awkward();
+    let items: Vec<&Todo> = todos.items.iter().collect();
+
+    let output = render(Format::Jsonl, &items);
+
+    assert_eq!(output.lines().count(), 2);
+    for line in output.lines() {
+        serde_json::from_str::<serde_json::Value>(line).unwrap();
+    }
+}
+
+#[test]
+fn csv_quotes_fields_and_tsv_escapes_them() {
+    let mut todos = awkward();
+    todos.set_notes(1, "line\twith tab").unwrap();
+    let items: Vec<&Todo> = todos.items.iter().collect();
+
+    let csv = render(Format::Csv, &items);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert!(lines[0].starts_with("id,title,done,status,due,priority,tags,notes"));
+    assert!(lines[1].starts_with("1,\"Buy milk, eggs\",true,done,"));
+    assert!(lines[2].starts_with("2,\"Say \"\"hi\"\" | wave\",false,open,,none,home social,"));
+
+    let tsv = render(Format::Tsv, &items);
+    assert!(tsv.lines().nth(1).unwrap().contains("line\\twith tab"));
+}
+
+#[test]
+fn table_and_markdown_summarize() {
+    let todos = awkward();
+    let items: Vec<&Todo> = todos.items.iter().collect();
+
+    let table = render(Format::Table, &items);
+    assert_eq!(
+        table.lines().next().unwrap(),
+        "ID  STATUS  PRIORITY  DUE  TITLE            TAGS"
+    );
+
+    let markdown = render(Format::Markdown, &items);
+    let lines: Vec<&str> = markdown.lines().collect();
+    assert_eq!(lines[0], "| id | status | priority | due | title | tags |");
+    assert_eq!(lines[1], "| --- | --- | --- | --- | --- | --- |");
+    assert_eq!(
+        lines[3],
+        "| 2 | open | none |  | Say \"hi\" \\| wave | home social |"
+    );
+}
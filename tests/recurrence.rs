@@ -0,0 +1,102 @@
+use chrono::NaiveDate;
+use todo::shared::recurrence::{Recurrence, Unit};
+
+mod common;
+use common::date;
+
+#[test]
+fn parse_named_rules() {
+    assert_eq!(
+        "daily".parse(),
+        Ok(Recurrence::Interval {
+            every: 1,
+            unit: Unit::Day
+        })
+    );
+    assert_eq!(
+        "Monthly".parse(),
+        Ok(Recurrence::Interval {
+            every: 1,
+            unit: Unit::Month
+        })
+    );
+}
+
+#[test]
+fn parse_interval_rules() {
+    assert_eq!(
+        "every 2 weeks".parse(),
+        Ok(Recurrence::Interval {
+            every: 2,
+            unit: Unit::Week
+        })
+    );
+    assert_eq!(
+        "every 1 day".parse(),
+        Ok(Recurrence::Interval {
+            every: 1,
+            unit: Unit::Day
+        })
+    );
+}
+
+#[test]
+fn parse_invalid_rules() {
+    for rule in [
+        "sometimes",
+        "every 0 days",
+        "every two weeks",
+        "every funday",
+        "every 500000 years",
+    ] {
+        assert_eq!(
+            rule.parse::<Recurrence>(),
+            Err(format!("Invalid recurrence '{}'", rule))
+        );
+    }
+}
+
+#[test]
+fn display_round_trip() {
+    for rule in [
+        "daily",
+        "weekly",
+        "yearly",
+        "every 3 months",
+        "every mon,fri",
+    ] {
+        let recurrence: Recurrence = rule.parse().unwrap();
+        assert_eq!(recurrence.to_string(), rule);
+    }
+}
+
+#[test]
+fn next_interval() {
+    let weekly: Recurrence = "every 2 weeks".parse().unwrap();
+    let monthly: Recurrence = "monthly".parse().unwrap();
+
+    assert_eq!(weekly.next(date(2030, 1, 1)), Ok(date(2030, 1, 15)));
+    assert_eq!(monthly.next(date(2030, 1, 31)), Ok(date(2030, 2, 28)));
+}
+
+#[test]
+fn next_weekday() {
+    // 2030-01-01 is a Tuesday
+    let rule: Recurrence = "every mon,thu".parse().unwrap();
+
+    assert_eq!(rule.next(date(2030, 1, 1)), Ok(date(2030, 1, 3)));
+    assert_eq!(rule.next(date(2030, 1, 3)), Ok(date(2030, 1, 7)));
+}
+
+#[test]
+fn next_out_of_range() {
+    let rule: Recurrence = "every 1000 years".parse().unwrap();
+
+    assert_eq!(
+        rule.next(NaiveDate::MAX),
+        Err(format!(
+            "The next 'every 1000 years' occurrence after {} is out of range",
+            NaiveDate::MAX
+        ))
+    );
+}
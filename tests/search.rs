@@ -0,0 +1,57 @@
+use todo::shared::search::{Field, Query, SearchMode, highlight, search};
+
+mod common;
+use common::searchable;
+
+#[test]
+fn substring_ignores_case_and_ranks_titles_first() {
+    let todos = searchable();
+    let query = Query::new("plumb", SearchMode::Substring).unwrap();
+
+    let hits = search(&todos, &query);
+
+    let ids: Vec<u64> = hits.iter().map(|(x, _)| x.id).collect();
+    assert_eq!(ids, vec![1, 2]);
+    assert_eq!(hits[1].1[0].field, Field::Notes);
+    assert_eq!(hits[1].1[0].spans, vec![(8, 13)]);
+}
+
+#[test]
+fn prefix_matches_word_starts_and_tags() {
+    let todos = searchable();
+
+    let query = Query::new("lumb", SearchMode::Prefix).unwrap();
+    assert!(search(&todos, &query).is_empty());
+
+    let query = Query::new("trav", SearchMode::Prefix).unwrap();
+    let hits = search(&todos, &query);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].1[0].field, Field::Tag);
+}
+
+#[test]
+fn regex_mode_and_errors() {
+    let todos = searchable();
+
+    let query = Query::new("^(book|call) ", SearchMode::Regex).unwrap();
+    assert_eq!(search(&todos, &query).len(), 2);
+
+    assert!(
+        Query::new("(unclosed", SearchMode::Regex)
+            .err()
+            .unwrap()
+            .starts_with("Invalid regex")
+    );
+    assert_eq!(
+        Query::new("  ", SearchMode::Substring).err(),
+        Some(String::from("The search query is empty"))
+    );
+}
+
+#[test]
+fn highlight_wraps_spans() {
+    assert_eq!(
+        highlight("Café au lait", &[(0, 4), (8, 12)], "<", ">"),
+        "<Café> au <lait>"
+    );
+}
@@ -0,0 +1,266 @@
+use serde_json::json;
+use todo::shared::history::{History, describe};
+use todo::shared::storage::{self, Backend, Storage};
+use todo::shared::store::{DEFAULT_PROJECT, Store};
+use todo::shared::todo::Priority;
+
+mod common;
+use common::projects;
+
+fn assert_round_trip(storage: &dyn Storage) {
+    let mut store = projects();
+    storage.save(&store).unwrap();
+
+    let loaded = storage.load().unwrap();
+    assert_eq!(loaded.names().collect::<Vec<_>>(), vec!["default", "work"]);
+    let todos = loaded.project(DEFAULT_PROJECT).unwrap();
+    assert_eq!(todos.items.len(), 2);
+    assert_eq!(todos.items[1].priority, Priority::High);
+    assert!(todos.items[1].has_tag("home"));
+    assert_eq!(todos.next_id(), 3);
+
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .remove(1)
+        .unwrap();
+    store.rename("work", "office").unwrap();
+    storage.save(&store).unwrap();
+
+    let loaded = storage.load().unwrap();
+    assert_eq!(
+        loaded.names().collect::<Vec<_>>(),
+        vec!["default", "office"]
+    );
+    assert_eq!(loaded.project(DEFAULT_PROJECT).unwrap().items.len(), 1);
+    assert_eq!(loaded.project("office").unwrap().items.len(), 1);
+}
+
+#[test]
+fn json_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+
+    assert_round_trip(storage.as_ref());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Sqlite, dir.path().join("todo.db")).unwrap();
+
+    assert_round_trip(storage.as_ref());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_empty_database_loads_empty_store() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Sqlite, dir.path().join("todo.db")).unwrap();
+
+    let store = storage.load().unwrap();
+
+    assert!(store.project(DEFAULT_PROJECT).unwrap().items.is_empty());
+}
+
+#[cfg(not(feature = "sqlite"))]
+#[test]
+fn sqlite_needs_feature() {
+    let result = storage::open(Backend::Sqlite, "todo.db".into());
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn archive_moves_done_todos_out_of_the_store() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+    let mut store = projects();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .mark(2, true)
+        .unwrap();
+    store.project_mut("work").unwrap().mark(1, true).unwrap();
+    storage.save(&store).unwrap();
+
+    assert_eq!(storage.archive(&mut store, Some("work"), None).unwrap(), 1);
+    assert_eq!(storage.archive(&mut store, Some("work"), None).unwrap(), 0);
+
+    let archived = storage.load_archive().unwrap();
+    assert_eq!(
+        archived.project("work").unwrap().items[0].title,
+        "Write report"
+    );
+    assert!(archived.project(DEFAULT_PROJECT).unwrap().items.is_empty());
+    let loaded = storage.load().unwrap();
+    assert!(loaded.project("work").unwrap().items.is_empty());
+    assert_eq!(loaded.project(DEFAULT_PROJECT).unwrap().items.len(), 2);
+}
+
+#[test]
+fn undoing_archive_moves_todos_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+    let mut store = projects();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .mark(1, true)
+        .unwrap();
+    storage.save(&store).unwrap();
+    storage.archive(&mut store, None, None).unwrap();
+
+    let mut history = storage.load_history().unwrap();
+    let mut archive = storage.load_archive().unwrap();
+    assert_eq!(
+        history.undo(&mut store, &mut archive),
+        Some(String::from("archive 1 todo"))
+    );
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().items.len(), 2);
+    assert!(archive.project(DEFAULT_PROJECT).unwrap().items.is_empty());
+
+    history.redo(&mut store, &mut archive);
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().items.len(), 1);
+    assert_eq!(archive.project(DEFAULT_PROJECT).unwrap().items.len(), 1);
+}
+
+fn assert_history_round_trip(storage: &dyn Storage) {
+    let before = projects();
+    let mut store = before.clone();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .update_title(1, "Buy oat milk")
+        .unwrap();
+    storage.save(&store).unwrap();
+    let mut history = History::default();
+    history.record(describe(&before, &store), &before, &store);
+    storage.save_history(&history).unwrap();
+
+    let mut loaded = storage.load_history().unwrap();
+    assert_eq!(loaded, history);
+
+    let mut archive = Store::new();
+    loaded.undo(&mut store, &mut archive).unwrap();
+    storage.save_history(&loaded).unwrap();
+    assert_eq!(store, before);
+    assert_eq!(storage.load_history().unwrap(), loaded);
+}
+
+#[test]
+fn json_history_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+
+    assert_history_round_trip(storage.as_ref());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_keeps_history_in_the_database() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Sqlite, dir.path().join("todo.db")).unwrap();
+
+    assert_history_round_trip(storage.as_ref());
+    assert!(!dir.path().join("todo.db.history.json").exists());
+}
+
+// Saves two adds and a history of them as written before entries held
+// changes, each a copy of the store from before its operation
+fn save_snapshot_history(storage: &dyn Storage) -> Store {
+    let empty = Store::new();
+    let mut one = empty.clone();
+    one.project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add(String::from("Buy milk"));
+    let mut two = one.clone();
+    two.project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add(String::from("Walk dog"));
+    storage.save(&two).unwrap();
+
+    let entry = |operation: &str, store: &Store| json!({ "operation": operation, "at": "2025-01-01T00:00:00Z", "store": store });
+    let history = json!({
+        "undo": [entry("add [1] Buy milk", &empty), entry("add [2] Walk dog", &one)],
+        "redo": [],
+    });
+    let path = format!("{}.history.json", storage.path().display());
+    std::fs::write(path, history.to_string()).unwrap();
+
+    storage.load().unwrap()
+}
+
+fn assert_snapshot_history_converted(storage: &dyn Storage) {
+    let mut store = save_snapshot_history(storage);
+    let mut archive = Store::new();
+
+    let mut history = storage.load_history().unwrap();
+    assert_eq!(
+        history.undo(&mut store, &mut archive),
+        Some(String::from("add [2] Walk dog"))
+    );
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().items.len(), 1);
+    assert_eq!(
+        history.undo(&mut store, &mut archive),
+        Some(String::from("add [1] Buy milk"))
+    );
+    assert!(store.project(DEFAULT_PROJECT).unwrap().items.is_empty());
+    assert_eq!(history.undo(&mut store, &mut archive), None);
+}
+
+#[test]
+fn json_converts_snapshot_history() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+
+    assert_snapshot_history_converted(storage.as_ref());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_imports_snapshot_history() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Sqlite, dir.path().join("todo.db")).unwrap();
+
+    assert_snapshot_history_converted(storage.as_ref());
+    assert!(!dir.path().join("todo.db.history.json").exists());
+    assert_eq!(storage.load_history().unwrap().undo_entries().len(), 2);
+}
+
+#[test]
+fn json_converts_snapshot_history_with_archiving() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = storage::open(Backend::Json, dir.path().join("todo.json")).unwrap();
+    let mut before = projects();
+    before
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .mark(1, true)
+        .unwrap();
+    let mut store = before.clone();
+    storage.save(&store).unwrap();
+    storage.archive(&mut store, None, None).unwrap();
+    let history = json!({
+        "undo": [{
+            "operation": "archive 1 todo",
+            "at": "2025-01-01T00:00:00Z",
+            "store": before,
+            "archive": Store::new(),
+        }],
+        "redo": [],
+    });
+    std::fs::write(
+        dir.path().join("todo.json.history.json"),
+        history.to_string(),
+    )
+    .unwrap();
+
+    let mut history = storage.load_history().unwrap();
+    let mut archive = storage.load_archive().unwrap();
+    history.undo(&mut store, &mut archive).unwrap();
+
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().items.len(), 2);
+    assert!(archive.project(DEFAULT_PROJECT).unwrap().items.is_empty());
+}
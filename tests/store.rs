@@ -0,0 +1,140 @@
+use chrono::{Duration, Utc};
+use todo::shared::store::{DEFAULT_PROJECT, Store};
+use todo::shared::todo::TodoList;
+
+#[test]
+fn new_store_has_default_project() {
+    let store = Store::new();
+    let names: Vec<&String> = store.names().collect();
+
+    assert_eq!(names, vec![DEFAULT_PROJECT]);
+    assert!(store.project(DEFAULT_PROJECT).unwrap().list().is_empty());
+}
+
+#[test]
+fn create_project() {
+    let mut store = Store::new();
+    let _ = store.create("work");
+
+    assert!(store.project("work").is_ok());
+    assert_eq!(
+        store.create("work"),
+        Err(String::from("Project 'work' already exists"))
+    );
+}
+
+#[test]
+fn create_project_invalid_name() {
+    let mut store = Store::new();
+    assert_eq!(
+        store.create(" "),
+        Err(String::from("Invalid project name ''"))
+    );
+}
+
+#[test]
+fn project_not_existing() {
+    let mut store = Store::new();
+    assert_eq!(
+        store.project_mut("work").err(),
+        Some(String::from("Project 'work' is not found"))
+    );
+}
+
+#[test]
+fn rename_project() {
+    let mut store = Store::new();
+    let _ = store.create("work");
+    store.project_mut("work").unwrap().add("Task".into());
+
+    let _ = store.rename("work", "job");
+
+    assert!(store.project("work").is_err());
+    assert_eq!(store.project("job").unwrap().list().len(), 1);
+}
+
+#[test]
+fn rename_default_project() {
+    let mut store = Store::new();
+    assert_eq!(
+        store.rename(DEFAULT_PROJECT, "job"),
+        Err(String::from("The default project cannot be renamed"))
+    );
+}
+
+#[test]
+fn delete_project() {
+    let mut store = Store::new();
+    let _ = store.create("work");
+    store.project_mut("work").unwrap().add("Task".into());
+
+    let removed = store.delete("work").unwrap();
+
+    assert_eq!(removed.list().len(), 1);
+    assert!(store.project("work").is_err());
+}
+
+#[test]
+fn delete_default_project() {
+    let mut store = Store::new();
+    assert!(store.delete(DEFAULT_PROJECT).is_err());
+    assert!(store.project(DEFAULT_PROJECT).is_ok());
+}
+
+#[test]
+fn move_todo() {
+    let mut store = Store::new();
+    let _ = store.create("work");
+    store.project_mut("work").unwrap().add("Existing".into());
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add("Task".into());
+    let _ = store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add_tag(1, "backend");
+
+    let new_id = store.move_todo(DEFAULT_PROJECT, 1, "work").unwrap();
+
+    assert_eq!(new_id, 2);
+    assert!(store.project(DEFAULT_PROJECT).unwrap().list().is_empty());
+    let moved = store.project("work").unwrap().get(2).unwrap();
+    assert_eq!(moved.title, "Task");
+    assert!(moved.has_tag("backend"));
+}
+
+#[test]
+fn move_todo_to_missing_project_keeps_todo() {
+    let mut store = Store::new();
+    store
+        .project_mut(DEFAULT_PROJECT)
+        .unwrap()
+        .add("Task".into());
+
+    assert!(store.move_todo(DEFAULT_PROJECT, 1, "work").is_err());
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().list().len(), 1);
+}
+
+#[test]
+fn load_legacy_list_into_default_project() {
+    let data = r#"{"items":[{"id":1,"title":"Old task","done":false}],"next_id":2}"#;
+    let list: TodoList = serde_json::from_str(data).unwrap();
+    let store = Store::from(list);
+
+    assert_eq!(store.project(DEFAULT_PROJECT).unwrap().list().len(), 1);
+}
+
+#[test]
+fn purge_trash_across_projects() {
+    let mut store = Store::new();
+    let _ = store.create("work");
+    for name in [DEFAULT_PROJECT, "work"] {
+        let todos = store.project_mut(name).unwrap();
+        todos.add(String::from("Test"));
+        let _ = todos.remove(1);
+    }
+
+    assert_eq!(store.purge_trash(Utc::now() - Duration::days(1)), 0);
+    assert_eq!(store.purge_trash(Utc::now() + Duration::days(1)), 2);
+}
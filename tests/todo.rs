@@ -1,11 +1,15 @@
-use todo::shared::todo::{Todo, TodoList};
+use chrono::{Duration, NaiveDate, Utc};
+use todo::shared::todo::{Priority, SortBy, Status, Todo, TodoList};
+
+mod common;
+use common::date;
 
 #[test]
 fn add_item() {
     let mut todos = TodoList::new();
     todos.add(String::from("Take a dog out"));
     assert!(exist(&todos.items, "Take a dog out", 1));
-    assert_eq!(find(&todos.items, 1).unwrap().done, false);
+    assert!(!find(&todos.items, 1).unwrap().is_done());
 }
 
 #[test]
@@ -23,9 +27,9 @@ fn mark_item() {
     let mut todo = TodoList::new();
     todo.add(String::from("Take a dog out"));
     let _ = todo.mark(1, true);
-    assert_eq!(find(&todo.items, 1).unwrap().done, true);
+    assert!(find(&todo.items, 1).unwrap().is_done());
     let _ = todo.mark(1, false);
-    assert_eq!(find(&todo.items, 1).unwrap().done, false);
+    assert!(!find(&todo.items, 1).unwrap().is_done());
 }
 
 #[test]
@@ -89,7 +93,7 @@ fn update_title() {
     let _ = todo.update_title(1, "Updated task");
 
     assert!(exist(&todo.items, "Updated task", 1));
-    assert_eq!(exist(&todo.items, "First task", 1), false);
+    assert!(!exist(&todo.items, "First task", 1));
 }
 
 #[test]
@@ -110,7 +114,600 @@ fn remove_item() {
     assert!(exist(&todo.items, "First task", 1));
 
     let _ = todo.remove(1);
-    assert_eq!(exist(&todo.items, "First task", 1), false);
+    assert!(!exist(&todo.items, "First task", 1));
+}
+
+#[test]
+fn set_due() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    let due = date(2030, 1, 31);
+
+    let _ = todo.set_due(1, Some(due));
+    assert_eq!(find(&todo.items, 1).unwrap().due, Some(due));
+
+    let _ = todo.set_due(1, None);
+    assert_eq!(find(&todo.items, 1).unwrap().due, None);
+}
+
+#[test]
+fn set_due_not_existing() {
+    let mut todo = TodoList::new();
+    assert_eq!(todo.set_due(1, None), Err(String::from("Todo 1 not found")));
+}
+
+#[test]
+fn list_items_by_due_date() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Overdue task"));
+    todo.add(String::from("Today task"));
+    todo.add(String::from("Upcoming task"));
+    todo.add(String::from("No due task"));
+    todo.add(String::from("Done overdue task"));
+    let today = date(2030, 6, 15);
+    let _ = todo.set_due(1, Some(date(2030, 6, 14)));
+    let _ = todo.set_due(2, Some(today));
+    let _ = todo.set_due(3, Some(date(2030, 6, 16)));
+    let _ = todo.set_due(5, Some(date(2030, 6, 1)));
+    let _ = todo.mark(5, true);
+
+    let overdue: Vec<&Todo> = todo.overdue(today).collect();
+    let due_today: Vec<&Todo> = todo.due_today(today).collect();
+    let upcoming: Vec<&Todo> = todo.upcoming(today).collect();
+
+    assert!(exist(overdue.iter().copied(), "Overdue task", 1));
+    assert_eq!(overdue.len(), 1);
+    assert!(exist(due_today.iter().copied(), "Today task", 2));
+    assert_eq!(due_today.len(), 1);
+    assert!(exist(upcoming.iter().copied(), "Upcoming task", 3));
+    assert_eq!(upcoming.len(), 1);
+}
+
+#[test]
+fn set_priority() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    assert_eq!(find(&todo.items, 1).unwrap().priority, Priority::None);
+
+    let _ = todo.set_priority(1, Priority::High);
+    assert_eq!(find(&todo.items, 1).unwrap().priority, Priority::High);
+}
+
+#[test]
+fn sort_by_priority() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("No priority"));
+    todo.add(String::from("Low"));
+    todo.add(String::from("High"));
+    todo.add(String::from("Another high"));
+    let _ = todo.set_priority(2, Priority::Low);
+    let _ = todo.set_priority(3, Priority::High);
+    let _ = todo.set_priority(4, Priority::High);
+
+    let mut items: Vec<&Todo> = todo.todo().collect();
+    SortBy::Priority.sort(&mut items);
+    let ids: Vec<u64> = items.iter().map(|x| x.id).collect();
+    assert_eq!(ids, vec![3, 4, 2, 1]);
+
+    SortBy::Id.sort(&mut items);
+    let ids: Vec<u64> = items.iter().map(|x| x.id).collect();
+    assert_eq!(ids, vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn add_and_remove_tags() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+
+    assert_eq!(todo.add_tag(1, "+Backend"), Ok(true));
+    assert_eq!(todo.add_tag(1, "backend"), Ok(false));
+    assert!(find(&todo.items, 1).unwrap().has_tag("+backend"));
+
+    assert_eq!(todo.remove_tag(1, "backend"), Ok(true));
+    assert_eq!(todo.remove_tag(1, "backend"), Ok(false));
+    assert!(find(&todo.items, 1).unwrap().tags.is_empty());
+}
+
+#[test]
+fn add_invalid_tag() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+
+    assert_eq!(todo.add_tag(1, "+"), Err(String::from("Invalid tag '+'")));
+    assert_eq!(
+        todo.add_tag(1, "two words"),
+        Err(String::from("Invalid tag 'two words'"))
+    );
+}
+
+#[test]
+fn tags_survive_title_update() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    let _ = todo.add_tag(1, "backend");
+    let _ = todo.update_title(1, "Updated task");
+
+    assert!(find(&todo.items, 1).unwrap().has_tag("backend"));
+}
+
+#[test]
+fn add_child() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Parent"));
+    let child = todo.add_child(1, String::from("Child")).unwrap();
+
+    assert_eq!(child.parent, Some(1));
+    let children: Vec<&Todo> = todo.children(1).collect();
+    assert!(exist(children.iter().copied(), "Child", 2));
+    assert_eq!(children.len(), 1);
+}
+
+#[test]
+fn add_child_parent_not_existing() {
+    let mut todo = TodoList::new();
+    assert_eq!(
+        todo.add_child(1, String::from("Child")).err(),
+        Some(String::from("Todo 1 not found"))
+    );
+    assert!(todo.items.is_empty());
+}
+
+#[test]
+fn mark_tree() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Parent"));
+    let _ = todo.add_child(1, String::from("Child"));
+    let _ = todo.add_child(2, String::from("Grandchild"));
+    let _ = todo.add_child(1, String::from("Done child"));
+    let _ = todo.mark(4, true);
+
+    assert_eq!(todo.open_descendants(1), vec![2, 3]);
+    assert_eq!(todo.mark_tree(1, true), Ok(vec![1, 2, 3]));
+    assert!(todo.open_descendants(1).is_empty());
+    assert_eq!(todo.todo().count(), 0);
+}
+
+#[test]
+fn force_mark_tree_with_blocked_subtask() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Parent"));
+    let _ = todo.add_child(1, String::from("Child"));
+    todo.add(String::from("Blocker"));
+    let _ = todo.add_blocker(2, 3);
+
+    assert!(todo.mark_tree(1, true).is_err());
+    assert_eq!(todo.todo().count(), 3);
+    assert_eq!(todo.force_mark_tree(1, true), Ok(vec![1, 2]));
+    assert_eq!(todo.todo().count(), 1);
+}
+
+#[test]
+fn remove_item_with_subtasks() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Parent"));
+    let _ = todo.add_child(1, String::from("Child"));
+    let _ = todo.add_child(2, String::from("Grandchild"));
+    todo.add(String::from("Other"));
+
+    let _ = todo.remove(1);
+
+    assert!(exist(&todo.items, "Other", 4));
+    assert_eq!(todo.items.len(), 1);
+}
+
+#[test]
+fn removed_items_can_be_restored_with_subtasks() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Parent"));
+    let _ = todo.add_child(1, String::from("Child"));
+    let _ = todo.add_child(2, String::from("Grandchild"));
+
+    let _ = todo.remove(2);
+    assert_eq!(todo.trash.len(), 2);
+    assert_eq!(todo.trash[1].todo.parent, Some(2));
+
+    todo.restore(2).unwrap();
+    assert!(todo.trash.is_empty());
+    assert_eq!(find(&todo.items, 2).unwrap().parent, Some(1));
+    assert_eq!(find(&todo.items, 3).unwrap().parent, Some(2));
+}
+
+#[test]
+fn restore_drops_links_to_missing_todos() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Parent"));
+    let _ = todo.add_child(1, String::from("Child"));
+    todo.add(String::from("Blocker"));
+    let _ = todo.add_blocker(2, 3);
+    let _ = todo.remove(2);
+    let _ = todo.remove(1);
+    let _ = todo.remove(3);
+
+    todo.restore(2).unwrap();
+
+    let restored = find(&todo.items, 2).unwrap();
+    assert_eq!(restored.parent, None);
+    assert!(restored.blocked_by.is_empty());
+    assert_eq!(todo.trash.len(), 2);
+    assert_eq!(
+        todo.restore(2),
+        Err(String::from("Todo 2 is not in the trash"))
+    );
+}
+
+#[test]
+fn purge_keeps_recently_trashed() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    let _ = todo.remove(1);
+
+    assert_eq!(todo.purge(Some(Utc::now() - Duration::days(1))), 0);
+    assert_eq!(todo.purge(None), 1);
+    assert!(todo.trash.is_empty());
+}
+
+#[test]
+fn take_done_waits_for_open_subtasks() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Parent"));
+    let _ = todo.add_child(1, String::from("Child"));
+    let _ = todo.add_child(1, String::from("Done child"));
+    todo.add(String::from("Other"));
+    let _ = todo.force_mark(1, true);
+    let _ = todo.mark(3, true);
+    let _ = todo.mark(4, true);
+
+    let taken: Vec<u64> = todo.take_done(None).iter().map(|x| x.id).collect();
+    assert_eq!(taken, vec![3, 4]);
+    assert_eq!(todo.items.len(), 2);
+
+    let _ = todo.mark(2, true);
+    let taken = todo.take_done(None);
+    assert_eq!(taken.len(), 2);
+    assert_eq!(find(&taken, 2).unwrap().parent, Some(1));
+}
+
+#[test]
+fn take_done_before_cutoff() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    let _ = todo.mark(1, true);
+
+    assert!(
+        todo.take_done(Some(Utc::now() - Duration::days(1)))
+            .is_empty()
+    );
+    assert_eq!(
+        todo.take_done(Some(Utc::now() + Duration::days(1))).len(),
+        1
+    );
+}
+
+#[test]
+fn take_done_without_completed_at() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Old task"));
+    let _ = todo.mark(1, true);
+    todo.items[0].completed_at = None;
+
+    assert!(
+        todo.take_done(Some(Utc::now() + Duration::days(1)))
+            .is_empty()
+    );
+    assert_eq!(todo.take_done(None).len(), 1);
+}
+
+#[test]
+fn put_archived_keeps_ids_unless_taken() {
+    let mut archive = TodoList::new();
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    let _ = todo.mark(1, true);
+    archive.put_archived(todo.take_done(None));
+
+    let mut todo = TodoList::new();
+    todo.add(String::from("Reused id"));
+    let _ = todo.mark(1, true);
+    archive.put_archived(todo.take_done(None));
+
+    assert!(exist(&archive.items, "First task", 1));
+    assert!(exist(&archive.items, "Reused id", 2));
+}
+
+#[test]
+fn take_item_detaches_subtasks() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Parent"));
+    let _ = todo.add_child(1, String::from("Child"));
+
+    let _ = todo.take(1);
+
+    assert_eq!(find(&todo.items, 2).unwrap().parent, None);
+}
+
+#[test]
+fn add_blocker() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    todo.add(String::from("Second task"));
+
+    assert_eq!(todo.add_blocker(2, 1), Ok(true));
+    assert_eq!(todo.add_blocker(2, 1), Ok(false));
+    assert_eq!(todo.open_blockers(2), vec![1]);
+}
+
+#[test]
+fn add_blocker_not_existing() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+
+    assert_eq!(
+        todo.add_blocker(1, 2),
+        Err(String::from("Todo 2 not found"))
+    );
+    assert_eq!(
+        todo.add_blocker(2, 1),
+        Err(String::from("Todo 2 not found"))
+    );
+}
+
+#[test]
+fn add_blocker_rejects_cycles() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    todo.add(String::from("Second task"));
+    todo.add(String::from("Third task"));
+    let _ = todo.add_blocker(2, 1);
+    let _ = todo.add_blocker(3, 2);
+
+    assert_eq!(
+        todo.add_blocker(1, 3),
+        Err(String::from(
+            "Todo 3 blocking 1 would create a dependency cycle"
+        ))
+    );
+    assert!(todo.add_blocker(1, 1).is_err());
+}
+
+#[test]
+fn mark_blocked_item() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    todo.add(String::from("Second task"));
+    let _ = todo.add_blocker(2, 1);
+
+    assert_eq!(
+        todo.mark(2, true),
+        Err(String::from("Todo 2 is blocked by 1"))
+    );
+    assert!(!find(&todo.items, 2).unwrap().is_done());
+
+    let _ = todo.mark(1, true);
+    assert_eq!(todo.mark(2, true), Ok(()));
+}
+
+#[test]
+fn force_mark_blocked_item() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    todo.add(String::from("Second task"));
+    let _ = todo.add_blocker(2, 1);
+
+    assert_eq!(todo.force_mark(2, true), Ok(()));
+    assert!(find(&todo.items, 2).unwrap().is_done());
+}
+
+#[test]
+fn list_items_blocked_and_ready() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    todo.add(String::from("Second task"));
+    todo.add(String::from("Third task"));
+    let _ = todo.add_blocker(2, 1);
+    let _ = todo.add_blocker(3, 2);
+    let _ = todo.mark(1, true);
+
+    let blocked: Vec<&Todo> = todo.blocked().collect();
+    let ready: Vec<&Todo> = todo.ready().collect();
+
+    assert!(exist(blocked.iter().copied(), "Third task", 3));
+    assert_eq!(blocked.len(), 1);
+    assert!(exist(ready.iter().copied(), "Second task", 2));
+    assert_eq!(ready.len(), 1);
+}
+
+#[test]
+fn remove_item_clears_blocker() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    todo.add(String::from("Second task"));
+    let _ = todo.add_blocker(2, 1);
+
+    let _ = todo.remove(1);
+
+    assert!(find(&todo.items, 2).unwrap().blocked_by.is_empty());
+}
+
+#[test]
+fn mark_recurring_item_creates_next() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Weekly report"));
+    let _ = todo.set_due(1, Some(date(2030, 1, 4)));
+    let _ = todo.set_recurrence(1, "weekly".parse().ok());
+
+    let _ = todo.mark(1, true);
+
+    let done = find(&todo.items, 1).unwrap();
+    assert!(done.is_done());
+    assert_eq!(done.recurrence, None);
+
+    let next = find(&todo.items, 2).unwrap();
+    assert_eq!(next.title, "Weekly report");
+    assert!(!next.is_done());
+    assert_eq!(next.due, Some(date(2030, 1, 11)));
+    assert_eq!(next.recurrence, "weekly".parse().ok());
+}
+
+#[test]
+fn mark_recurring_item_twice_creates_one_next() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Standup"));
+    let _ = todo.set_recurrence(1, "daily".parse().ok());
+
+    let _ = todo.mark(1, true);
+    let _ = todo.mark(1, false);
+    let _ = todo.mark(1, true);
+
+    assert_eq!(todo.items.len(), 2);
+    assert_eq!(todo.recurring().count(), 1);
+}
+
+#[test]
+fn mark_recurring_item_out_of_range_fails() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Far future"));
+    let _ = todo.set_due(1, Some(NaiveDate::MAX));
+    let _ = todo.set_recurrence(1, "yearly".parse().ok());
+
+    assert!(todo.mark(1, true).is_err());
+
+    assert_eq!(todo.items.len(), 1);
+    assert!(!todo.items[0].is_done());
+    assert!(todo.items[0].recurrence.is_some());
+}
+
+#[test]
+fn timestamps_on_add() {
+    let mut todo = TodoList::new();
+    let added = todo.add(String::from("First task"));
+
+    assert!(added.created_at.is_some());
+    assert_eq!(added.updated_at, added.created_at);
+    assert_eq!(added.completed_at, None);
+}
+
+#[test]
+fn timestamps_on_mark() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    let created = find(&todo.items, 1).unwrap().created_at;
+
+    let _ = todo.mark(1, true);
+    let marked = find(&todo.items, 1).unwrap();
+    assert!(marked.completed_at.is_some());
+    assert!(marked.updated_at >= created);
+    assert_eq!(marked.created_at, created);
+
+    let _ = todo.mark(1, false);
+    assert_eq!(find(&todo.items, 1).unwrap().completed_at, None);
+}
+
+#[test]
+fn timestamps_on_update() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    let before = find(&todo.items, 1).unwrap().updated_at;
+
+    let _ = todo.update_title(1, "Updated task");
+
+    assert!(find(&todo.items, 1).unwrap().updated_at >= before);
+}
+
+#[test]
+fn sort_by_completed() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    todo.add(String::from("Second task"));
+    todo.add(String::from("Third task"));
+    let _ = todo.mark(3, true);
+    let _ = todo.mark(1, true);
+
+    let mut items: Vec<&Todo> = todo.list().iter().collect();
+    SortBy::Completed.sort(&mut items);
+    let ids: Vec<u64> = items.iter().map(|x| x.id).collect();
+
+    assert_eq!(ids, vec![3, 1, 2]);
+}
+
+#[test]
+fn set_status() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+
+    let _ = todo.set_status(1, Status::InProgress);
+    assert_eq!(find(&todo.items, 1).unwrap().status, Status::InProgress);
+    assert!(!find(&todo.items, 1).unwrap().is_done());
+
+    let _ = todo.set_status(1, Status::Cancelled);
+    assert!(find(&todo.items, 1).unwrap().is_done());
+    assert!(find(&todo.items, 1).unwrap().completed_at.is_some());
+    assert_eq!(todo.done().count(), 1);
+}
+
+#[test]
+fn set_status_done_respects_blockers() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    todo.add(String::from("Second task"));
+    let _ = todo.add_blocker(2, 1);
+
+    assert_eq!(
+        todo.set_status(2, Status::Done),
+        Err(String::from("Todo 2 is blocked by 1"))
+    );
+    assert_eq!(todo.set_status(2, Status::Waiting), Ok(()));
+}
+
+#[test]
+fn set_status_done_refuses_open_subtasks() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("Parent"));
+    let _ = todo.add_child(1, String::from("Child"));
+
+    assert_eq!(
+        todo.set_status(1, Status::Done),
+        Err(String::from(
+            "Todo 1 has 1 open subtasks, mark them done first"
+        ))
+    );
+    assert!(!find(&todo.items, 1).unwrap().is_done());
+
+    let _ = todo.set_status(2, Status::Done);
+    assert_eq!(todo.set_status(1, Status::Done), Ok(()));
+}
+
+#[test]
+fn status_is_stored_with_done_flag() {
+    let mut todo = TodoList::new();
+    todo.add(String::from("First task"));
+    let _ = todo.set_status(1, Status::InProgress);
+
+    let data = serde_json::to_value(&todo).unwrap();
+    assert_eq!(data["items"][0]["status"], "in-progress");
+    assert_eq!(data["items"][0]["done"], false);
+
+    let loaded: TodoList = serde_json::from_value(data).unwrap();
+    assert_eq!(find(&loaded.items, 1).unwrap().status, Status::InProgress);
+}
+
+#[test]
+fn load_list_with_done_flag_only() {
+    let data = r#"{"items":[{"id":1,"title":"Old task","done":true}],"next_id":2}"#;
+    let todo: TodoList = serde_json::from_str(data).unwrap();
+
+    assert_eq!(find(&todo.items, 1).unwrap().status, Status::Done);
+}
+
+#[test]
+fn load_list_without_due_dates() {
+    let data = r#"{"items":[{"id":1,"title":"Old task","done":false}],"next_id":2}"#;
+    let todo: TodoList = serde_json::from_str(data).unwrap();
+
+    assert!(exist(&todo.items, "Old task", 1));
+    assert_eq!(find(&todo.items, 1).unwrap().due, None);
+    assert_eq!(find(&todo.items, 1).unwrap().priority, Priority::None);
+    assert!(find(&todo.items, 1).unwrap().tags.is_empty());
+    assert_eq!(find(&todo.items, 1).unwrap().created_at, None);
 }
 
 fn exist<'a, I>(todos: I, title: &str, id: u64) -> bool